@@ -1,6 +1,5 @@
 import argparse
 import json
-import re
 from typing import List, Dict, Any
 import numpy as np
 from sklearn.preprocessing import LabelEncoder, OneHotEncoder
@@ -19,21 +18,19 @@ def load_data(file_path: str) -> List[Dict[str, Any]]:
         data = json.load(f)
     return data
 
-def extract_headers(response: str, excluded_headers: List[str]) -> Dict[str, str]:
+def extract_headers(sample: Dict[str, Any], excluded_headers: List[str]) -> Dict[str, str]:
     headers = {}
-    header_pattern = r"(\S+):\s*(.*?)\r\n"
-    matches = re.findall(header_pattern, response)
-    for header, value in matches:
+    parsed_response = sample.get("parsed_response") or {}
+    for header, value in parsed_response.get("headers", []):
         header_lower = header.lower()
         if header_lower not in excluded_headers:
             headers[header_lower] = value
     return headers
 
-def extract_status(response: str) -> tuple:
-    status_pattern = r"HTTP/\d\.\d (\d+) (.+)\r\n"
-    match = re.search(status_pattern, response)
-    if match:
-        return int(match.group(1)), match.group(2)
+def extract_status(sample: Dict[str, Any]) -> tuple:
+    parsed_response = sample.get("parsed_response")
+    if parsed_response and parsed_response["status_code"] is not None:
+        return parsed_response["status_code"], parsed_response["reason_phrase"]
     return None, None
 
 def get_capitalization(s: str) -> str:
@@ -47,9 +44,8 @@ def get_capitalization(s: str) -> str:
         return "other"
     
 def extract_features(sample: Dict[str, Any], all_headers: List[str], excluded_headers: List[str]) -> Dict[str, Any]:
-    response = sample["response"]
-    headers = extract_headers(response, excluded_headers)
-    status_code, status_message = extract_status(response)
+    headers = extract_headers(sample, excluded_headers)
+    status_code, status_message = extract_status(sample)
 
     header_order = [all_headers.index(header) if header in all_headers else -1 for header in headers]
     header_order += [-1] * (len(all_headers) - len(header_order))
@@ -75,7 +71,7 @@ def prepare_data(samples: List[Dict[str, Any]], all_headers: List[str], excluded
     if not all_headers:
         all_headers = set()
         for sample in samples:
-            headers = extract_headers(sample["response"], excluded_headers)
+            headers = extract_headers(sample, excluded_headers)
             all_headers.update(headers.keys())
         all_headers = sorted(list(all_headers))
 
@@ -1,4 +1,4 @@
-use clap::{command, Arg, ArgAction, ArgGroup};
+use clap::{command, value_parser, Arg, ArgAction, ArgGroup, Command};
 
 pub fn parse_args() -> clap::ArgMatches {
     command!()
@@ -15,17 +15,273 @@ pub fn parse_args() -> clap::ArgMatches {
                 .value_parser(["GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH"])
                 .default_values(["GET", "HEAD"]),
         )
+        .arg(
+            Arg::new("mutators")
+                .long("mutators")
+                .value_name("NAME_1,NAME_2,...")
+                .help("Only run these mutators: method, request-target, http-version, header, header-name, body, framing, spacing, raw-bytes")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("disable-mutators")
+                .long("disable-mutators")
+                .value_name("NAME_1,NAME_2,...")
+                .help("Skip these mutators")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("only")
+                .long("only")
+                .value_name("CATEGORY_1,CATEGORY_2,...")
+                .help("Only send mutations of these categories, e.g. method, request_target, http-version, header, spacing")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("skip")
+                .long("skip")
+                .value_name("CATEGORY_1,CATEGORY_2,...")
+                .help("Do not send mutations of these categories")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("only-deviations")
+                .long("only-deviations")
+                .help("Leave out the mutations whose response does not deviate from the baseline of its target and method")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("list-mutations")
+                .long("list-mutations")
+                .help("Print the planned mutations without sending anything")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Craft every request and write it to the output without sending anything")
+                .conflicts_with("checkpoint")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("mutations")
+                .long("mutations")
+                .value_name("FILE")
+                .help("Load mutation payloads from a TOML or YAML definition file instead of the built-in mutations/default.toml; can be repeated")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("normalization")
+                .long("normalization")
+                .value_name("FILE")
+                .help("Load the rules that mask dynamic values in normalized_response from a TOML or YAML file instead of the built-in normalization/default.toml; can be repeated")
+                .action(ArgAction::Append),
+        )
         .arg(Arg::new("output").short('o').long("output").value_name("FILE").help("Write output to a file"))
         .arg(
             Arg::new("output-format")
                 .long("output-format")
                 .short('f')
                 .value_name("FORMAT")
-                .help("Specify the output format")
-                .value_parser(["json", "csv"])
+                .help("Specify the output format; jsonl writes each result as soon as its request completes, so its lines are not sorted by request_index: baselines come first, then the mutations in request_index order, then the timing-sensitive framing mutations")
+                .value_parser(["json", "jsonl", "csv"])
                 .default_value("json"),
         )
+        .arg(
+            Arg::new("features")
+                .long("features")
+                .value_name("FILE")
+                .help("Also write the classifier feature vector of every response to this file"),
+        )
+        .arg(
+            Arg::new("features-format")
+                .long("features-format")
+                .value_name("FORMAT")
+                .help("Format of the features file")
+                .value_parser(["csv", "json"])
+                .default_value("csv"),
+        )
+        .arg(
+            Arg::new("feature-headers")
+                .long("feature-headers")
+                .value_name("HEADER_1,HEADER_2,...")
+                .help("Header vocabulary of the feature vectors, defaults to every header seen in the run")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("feature-exclude")
+                .long("feature-exclude")
+                .value_name("HEADER_1,HEADER_2,...")
+                .help("Headers left out of the feature vectors")
+                .value_delimiter(',')
+                .default_values(["date", "server"]),
+        )
+        .arg(
+            Arg::new("detect")
+                .long("detect")
+                .value_name("MODEL")
+                .help("Name the framework of every target with a model from `wmap train` once the run is over; the report goes to stderr")
+                .conflicts_with("dry-run"),
+        )
+        .arg(
+            signatures_arg()
+                .help("Score every target against the framework signatures in this TOML or YAML file once the run is over; can be repeated, the report goes to stderr")
+                .conflicts_with("dry-run"),
+        )
+        .arg(Arg::new("sni").long("sni").value_name("HOSTNAME").help("Override the TLS server name sent for https:// targets"))
+        .arg(Arg::new("insecure").short('k').long("insecure").help("Skip TLS certificate verification").action(ArgAction::SetTrue))
+        .arg(
+            Arg::new("ca-file")
+                .long("ca-file")
+                .value_name("FILE")
+                .help("PEM bundle of CA certificates to trust instead of the built-in roots"),
+        )
+        .arg(
+            Arg::new("connect-timeout")
+                .long("connect-timeout")
+                .value_name("MILLISECONDS")
+                .help("Maximum time to wait for the connection to be established")
+                .value_parser(value_parser!(u64))
+                .default_value("5000"),
+        )
+        .arg(
+            Arg::new("write-timeout")
+                .long("write-timeout")
+                .value_name("MILLISECONDS")
+                .help("Maximum time a single write of the request may block")
+                .value_parser(value_parser!(u64))
+                .default_value("5000"),
+        )
+        .arg(
+            Arg::new("read-timeout")
+                .long("read-timeout")
+                .value_name("MILLISECONDS")
+                .help("Maximum time to wait for the server to send more of the response")
+                .value_parser(value_parser!(u64))
+                .default_value("10000"),
+        )
+        .arg(
+            Arg::new("concurrency")
+                .short('c')
+                .long("concurrency")
+                .value_name("N")
+                .help("Maximum number of requests in flight across all targets")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("per-target-concurrency")
+                .long("per-target-concurrency")
+                .value_name("N")
+                .help("Maximum number of requests in flight to the same host and port [default: same as --concurrency]")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("samples")
+                .long("samples")
+                .value_name("N")
+                .help("Send every request N times and record min, median, p90 and standard deviation of its timings; only the first response is kept")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("rate")
+                .long("rate")
+                .value_name("REQUESTS_PER_SECOND")
+                .help("Maximum number of requests per second sent to the same host and port")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("delay")
+                .long("delay")
+                .value_name("MILLISECONDS")
+                .help("Minimum delay between two requests to the same host and port")
+                .value_parser(value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("max-retries")
+                .long("max-retries")
+                .value_name("N")
+                .help("Times a request throttled with 429 or 503 is retried after backing off")
+                .value_parser(value_parser!(u32))
+                .default_value("2"),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .value_name("FILE")
+                .help("Record every finished (target, method, mutation) tuple in this file"),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help("Skip the tuples already recorded in the checkpoint file and append to the output file; requires jsonl output")
+                .requires("checkpoint")
+                .action(ArgAction::SetTrue),
+        )
         .arg(Arg::new("verbose").short('v').long("verbose").help("Display additional information").action(ArgAction::SetTrue))
-        .group(ArgGroup::new("required_group").args(&["url", "input"]).required(true))
+        .group(ArgGroup::new("required_group").args(["url", "input"]).required(true))
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("train")
+                .about("Train a framework classifier from the json or jsonl results of labelled targets")
+                .arg(Arg::new("model").long("model").value_name("FILE").help("Where to write the model").required(true))
+                .arg(results_arg())
+                .arg(
+                    Arg::new("trees")
+                        .long("trees")
+                        .value_name("N")
+                        .help("Number of trees in the forest")
+                        .value_parser(value_parser!(usize))
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("max-depth")
+                        .long("max-depth")
+                        .value_name("N")
+                        .help("Maximum depth of a tree, unlimited by default")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("min-samples-split")
+                        .long("min-samples-split")
+                        .value_name("N")
+                        .help("Minimum number of responses a node needs before it is split")
+                        .value_parser(value_parser!(usize))
+                        .default_value("2"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_name("N")
+                        .help("Seed of the bootstrap samples and feature draws")
+                        .value_parser(value_parser!(u64))
+                        .default_value("22"),
+                )
+                .arg(
+                    Arg::new("feature-exclude")
+                        .long("feature-exclude")
+                        .value_name("HEADER_1,HEADER_2,...")
+                        .help("Headers the model ignores")
+                        .value_delimiter(',')
+                        .default_values(["date", "server"]),
+                ),
+        )
+        .subcommand(
+            Command::new("detect")
+                .about("Name the framework of every target in json or jsonl results with a trained model or signature rules")
+                .arg(Arg::new("model").long("model").value_name("FILE").help("Model written by `wmap train`"))
+                .arg(signatures_arg().help("Framework signatures in TOML or YAML; can be repeated, the built-in signatures/default.toml is used when neither this nor --model is given"))
+                .arg(results_arg()),
+        )
         .get_matches()
 }
+
+fn signatures_arg() -> Arg {
+    Arg::new("signatures").long("signatures").value_name("FILE").action(ArgAction::Append)
+}
+
+fn results_arg() -> Arg {
+    Arg::new("results").value_name("RESULTS").help("Results files written by the fuzzer").num_args(1..).required(true)
+}
@@ -0,0 +1,134 @@
+use crate::executor::RequestKey;
+use crate::http_client::response::ParsedResponse;
+use crate::logger::RequestResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// How a mutated response differs from the clean request of the same target and method
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineDiff {
+    pub baseline_index: u32,
+    pub deviates: bool,
+    pub status_changed: bool,
+    // Lowercased names
+    pub headers_added: Vec<String>,
+    pub headers_removed: Vec<String>,
+    pub body_length_delta: i64,
+    // Dice coefficient of the byte bigrams of both bodies, 1 for identical bodies
+    pub body_similarity: f64,
+}
+
+struct BaselineResponse {
+    network_baseline: Option<u64>,
+    status_code: Option<u16>,
+    header_names: Vec<String>,
+    body_length: usize,
+    body: Vec<u8>,
+}
+
+impl BaselineResponse {
+    fn from_result(result: &RequestResult) -> Option<Self> {
+        let exchange = result.exchange.as_ref()?;
+        let parsed = exchange.parsed_response.as_ref();
+
+        let mut header_names: Vec<String> = Vec::new();
+        for (name, _) in parsed.map(|p| p.head.headers.as_slice()).unwrap_or_default() {
+            let name = name.trim().to_lowercase();
+            if !header_names.contains(&name) {
+                header_names.push(name);
+            }
+        }
+
+        Some(Self {
+            network_baseline: exchange.timing.tcp_connect_us.as_ref().map(|tcp_connect| tcp_connect.min),
+            status_code: parsed.and_then(|p| p.head.status_code),
+            header_names,
+            body_length: parsed.map_or(0, |p| p.body_length),
+            // Tokens and timestamps in the body would otherwise make every response deviate
+            body: ParsedResponse::body(exchange.normalized_response.as_ref().unwrap_or(&exchange.response).as_bytes()).unwrap_or_default(),
+        })
+    }
+}
+
+// Baseline responses of a run, and which baseline every other request is compared with
+pub struct Baselines {
+    baseline_of: HashMap<u32, u32>,
+    responses: Mutex<HashMap<u32, BaselineResponse>>,
+}
+
+impl Baselines {
+    pub fn new(keys: &[RequestKey]) -> Self {
+        let baselines: HashMap<(usize, &str), u32> = keys.iter().filter(|key| key.baseline).map(|key| ((key.target_index, key.method.as_str()), key.request_index)).collect();
+
+        Self {
+            baseline_of: keys
+                .iter()
+                .filter(|key| !key.baseline)
+                .filter_map(|key| Some((key.request_index, *baselines.get(&(key.target_index, key.method.as_str()))?)))
+                .collect(),
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Keeps a baseline response for later, or diffs a mutated one against its baseline. Baselines run first, so theirs is known by then.
+    // Either way the timings get the network baseline of the target subtracted
+    pub fn annotate(&self, result: &mut RequestResult) {
+        let Some(baseline_index) = self.baseline_of.get(&result.request_index) else {
+            if let Some(response) = BaselineResponse::from_result(result) {
+                subtract_network_baseline(result, response.network_baseline);
+                self.responses.lock().unwrap().insert(result.request_index, response);
+            }
+            return;
+        };
+
+        let responses = self.responses.lock().unwrap();
+        let (Some(baseline), Some(response)) = (responses.get(baseline_index), BaselineResponse::from_result(result)) else {
+            return;
+        };
+        subtract_network_baseline(result, baseline.network_baseline);
+
+        let headers_added: Vec<String> = response.header_names.iter().filter(|name| !baseline.header_names.contains(name)).cloned().collect();
+        let headers_removed: Vec<String> = baseline.header_names.iter().filter(|name| !response.header_names.contains(name)).cloned().collect();
+        let status_changed = response.status_code != baseline.status_code;
+        let body_length_delta = response.body_length as i64 - baseline.body_length as i64;
+        let body_similarity = similarity(&baseline.body, &response.body);
+
+        result.baseline_diff = Some(BaselineDiff {
+            baseline_index: *baseline_index,
+            deviates: status_changed || !headers_added.is_empty() || !headers_removed.is_empty() || body_length_delta != 0 || body_similarity < 1.0,
+            status_changed,
+            headers_added,
+            headers_removed,
+            body_length_delta,
+            body_similarity,
+        });
+    }
+}
+
+fn subtract_network_baseline(result: &mut RequestResult, network_baseline: Option<u64>) {
+    if let (Some(exchange), Some(network_baseline)) = (&mut result.exchange, network_baseline) {
+        exchange.timing.subtract_network_baseline(network_baseline);
+    }
+}
+
+fn similarity(a: &[u8], b: &[u8]) -> f64 {
+    if a.len() < 2 || b.len() < 2 {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+
+    let mut bigrams: HashMap<&[u8], usize> = HashMap::new();
+    a.windows(2).for_each(|bigram| *bigrams.entry(bigram).or_default() += 1);
+
+    let mut shared = 0;
+    for bigram in b.windows(2) {
+        if let Some(count) = bigrams.get_mut(bigram).filter(|count| **count > 0) {
+            *count -= 1;
+            shared += 1;
+        }
+    }
+
+    // Rounded so identical runs write identical output
+    let similarity = 2.0 * shared as f64 / (a.len() + b.len() - 2) as f64;
+    (similarity * 10000.0).round() / 10000.0
+}
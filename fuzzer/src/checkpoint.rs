@@ -0,0 +1,105 @@
+use crate::executor::RequestKey;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::Mutex;
+
+// One finished (target, method, mutation) tuple per line of the checkpoint file
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointEntry {
+    target: String,
+    method: String,
+    mutation: String,
+    request_index: u32,
+}
+
+pub struct Checkpoint {
+    file: Mutex<io::BufWriter<File>>,
+    finished: HashSet<(String, String, String)>,
+    last_request_index: Option<u32>,
+    pending: Mutex<HashMap<u32, CheckpointEntry>>,
+}
+
+impl Checkpoint {
+    // Starts a new checkpoint file, or picks up the tuples already recorded in it when resuming
+    pub fn open(path: &str, resume: bool) -> Self {
+        let mut finished = HashSet::new();
+        let mut last_request_index = None;
+
+        if resume {
+            if let Ok(file) = File::open(path) {
+                // A line cut short by an interruption is simply sent again
+                for entry in BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| serde_json::from_str::<CheckpointEntry>(&line).ok())
+                {
+                    last_request_index = last_request_index.max(Some(entry.request_index));
+                    finished.insert((entry.target, entry.method, entry.mutation));
+                }
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(path)
+            .expect("Failed to open checkpoint file");
+
+        Self {
+            file: Mutex::new(io::BufWriter::new(file)),
+            finished,
+            last_request_index,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_args(matches: &clap::ArgMatches) -> Option<Self> {
+        matches.get_one::<String>("checkpoint").map(|path| Self::open(path, matches.get_flag("resume")))
+    }
+
+    pub fn last_request_index(&self) -> Option<u32> {
+        self.last_request_index
+    }
+
+    pub fn is_finished(&self, key: &RequestKey) -> bool {
+        self.finished.contains(&(key.url.clone(), key.method.clone(), key.mutation_description.clone()))
+    }
+
+    // Drops the requests finished in a previous run and remembers the rest so they can be recorded once written.
+    // Finished baselines are kept, as the remaining mutations are diffed against them, but they are not recorded again
+    pub fn skip_finished(&self, keys: Vec<RequestKey>) -> Vec<RequestKey> {
+        let mut pending = self.pending.lock().unwrap();
+
+        keys.into_iter()
+            .filter(|key| key.baseline || !self.is_finished(key))
+            .inspect(|key| {
+                if !self.is_finished(key) {
+                    pending.insert(
+                        key.request_index,
+                        CheckpointEntry {
+                            target: key.url.clone(),
+                            method: key.method.clone(),
+                            mutation: key.mutation_description.clone(),
+                            request_index: key.request_index,
+                        },
+                    );
+                }
+            })
+            .collect()
+    }
+
+    // Marks a request as finished. Only call this once its result has been written out
+    pub fn record(&self, request_index: u32) {
+        let Some(entry) = self.pending.lock().unwrap().remove(&request_index) else {
+            return;
+        };
+
+        let mut writer = self.file.lock().unwrap();
+        writeln!(writer, "{}", serde_json::to_string(&entry).expect("Failed to serialize checkpoint entry")).expect("Failed to write checkpoint file");
+        writer.flush().expect("Failed to flush checkpoint file");
+    }
+}
@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureKind {
+    Numeric,
+    // Holds an index into a category table, -1 for a value the table does not have
+    Categorical,
+}
+
+pub struct TrainingOptions {
+    pub trees: usize,
+    pub max_depth: Option<usize>,
+    pub min_samples_split: usize,
+    pub seed: u64,
+}
+
+// Numeric splits send a sample left when its value is at most the threshold, categorical ones when it equals the category
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Node {
+    Leaf { probabilities: Vec<f64> },
+    Threshold { feature: usize, threshold: f64, left: usize, right: usize },
+    Category { feature: usize, category: f64, left: usize, right: usize },
+}
+
+// The root is the first node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+}
+
+impl Tree {
+    pub fn probabilities(&self, sample: &[f64]) -> &[f64] {
+        let mut position = 0;
+        loop {
+            position = match &self.nodes[position] {
+                Node::Leaf { probabilities } => return probabilities,
+                Node::Threshold { feature, threshold, left, right } => {
+                    if sample[*feature] <= *threshold {
+                        *left
+                    } else {
+                        *right
+                    }
+                }
+                Node::Category { feature, category, left, right } => {
+                    if sample[*feature] == *category {
+                        *left
+                    } else {
+                        *right
+                    }
+                }
+            };
+        }
+    }
+}
+
+// A tree along with which samples its bootstrap sample drew
+type BaggedTree = (Tree, Vec<bool>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forest {
+    pub trees: Vec<Tree>,
+}
+
+impl Forest {
+    // Trains the trees on bootstrap samples in parallel, and also returns the out-of-bag accuracy when some sample was left out of a tree
+    pub fn train(samples: &[Vec<f64>], labels: &[usize], class_count: usize, kinds: &[FeatureKind], options: &TrainingOptions) -> (Self, Option<f64>) {
+        let next_tree = AtomicUsize::new(0);
+        let trained: Mutex<Vec<Option<BaggedTree>>> = Mutex::new(vec![None; options.trees]);
+        let workers = thread::available_parallelism().map_or(1, |workers| workers.get()).min(options.trees.max(1));
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let tree_index = next_tree.fetch_add(1, Ordering::Relaxed);
+                    if tree_index >= options.trees {
+                        break;
+                    }
+                    // Seeded per tree so the forest does not depend on how the trees were spread over threads
+                    let mut rng = Rng::new(options.seed.wrapping_add(tree_index as u64));
+                    let tree = train_tree(samples, labels, class_count, kinds, options, &mut rng);
+                    trained.lock().unwrap()[tree_index] = Some(tree);
+                });
+            }
+        });
+
+        let mut trees = Vec::with_capacity(options.trees);
+        let mut votes = vec![vec![0.0; class_count]; samples.len()];
+        for (tree, in_bag) in trained.into_inner().unwrap().into_iter().flatten() {
+            for (sample_index, _) in in_bag.iter().enumerate().filter(|(_, in_bag)| !**in_bag) {
+                for (vote, probability) in votes[sample_index].iter_mut().zip(tree.probabilities(&samples[sample_index])) {
+                    *vote += probability;
+                }
+            }
+            trees.push(tree);
+        }
+
+        let (mut voted, mut correct) = (0, 0);
+        for (sample_votes, label) in votes.iter().zip(labels) {
+            if sample_votes.iter().all(|vote| *vote == 0.0) {
+                continue;
+            }
+            voted += 1;
+            if argmax(sample_votes) == *label {
+                correct += 1;
+            }
+        }
+
+        (Self { trees }, (voted > 0).then(|| correct as f64 / voted as f64))
+    }
+
+    // Mean of the leaf distributions the sample reaches
+    pub fn probabilities(&self, sample: &[f64], class_count: usize) -> Vec<f64> {
+        let mut probabilities = vec![0.0; class_count];
+        for tree in &self.trees {
+            for (total, probability) in probabilities.iter_mut().zip(tree.probabilities(sample)) {
+                *total += probability;
+            }
+        }
+
+        let tree_count = self.trees.len().max(1) as f64;
+        probabilities.iter_mut().for_each(|probability| *probability /= tree_count);
+        probabilities
+    }
+}
+
+pub fn argmax(values: &[f64]) -> usize {
+    values.iter().enumerate().fold(0, |best, (index, value)| if *value > values[best] { index } else { best })
+}
+
+fn train_tree(samples: &[Vec<f64>], labels: &[usize], class_count: usize, kinds: &[FeatureKind], options: &TrainingOptions, rng: &mut Rng) -> BaggedTree {
+    let mut in_bag = vec![false; samples.len()];
+    let mut indices: Vec<usize> = (0..samples.len()).map(|_| rng.below(samples.len())).collect();
+    indices.iter().for_each(|index| in_bag[*index] = true);
+
+    let mut builder = TreeBuilder {
+        samples,
+        labels,
+        class_count,
+        kinds,
+        max_depth: options.max_depth,
+        min_samples_split: options.min_samples_split.max(2),
+        // The usual square root of the feature count for classification
+        features_per_split: ((kinds.len() as f64).sqrt() as usize).max(1),
+        rng,
+        nodes: Vec::new(),
+    };
+    builder.build(&mut indices, 0);
+
+    (Tree { nodes: builder.nodes }, in_bag)
+}
+
+struct Split {
+    node: Node,
+    impurity: f64,
+}
+
+struct TreeBuilder<'a> {
+    samples: &'a [Vec<f64>],
+    labels: &'a [usize],
+    class_count: usize,
+    kinds: &'a [FeatureKind],
+    max_depth: Option<usize>,
+    min_samples_split: usize,
+    features_per_split: usize,
+    rng: &'a mut Rng,
+    nodes: Vec<Node>,
+}
+
+impl TreeBuilder<'_> {
+    // Grows the subtree for these samples and returns the position of its root
+    fn build(&mut self, indices: &mut [usize], depth: usize) -> usize {
+        let counts = self.class_counts(indices);
+        let impurity = gini(&counts, indices.len());
+        let position = self.nodes.len();
+        self.nodes.push(Node::Leaf {
+            probabilities: counts.iter().map(|count| *count as f64 / indices.len() as f64).collect(),
+        });
+
+        if impurity == 0.0 || indices.len() < self.min_samples_split || self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return position;
+        }
+        let Some(split) = self.best_split(indices, &counts, impurity) else {
+            return position;
+        };
+
+        let mut left_count = 0;
+        for index in 0..indices.len() {
+            if goes_left(&split.node, &self.samples[indices[index]]) {
+                indices.swap(index, left_count);
+                left_count += 1;
+            }
+        }
+
+        let (left_indices, right_indices) = indices.split_at_mut(left_count);
+        let left = self.build(left_indices, depth + 1);
+        let right = self.build(right_indices, depth + 1);
+        self.nodes[position] = match split.node {
+            Node::Threshold { feature, threshold, .. } => Node::Threshold { feature, threshold, left, right },
+            Node::Category { feature, category, .. } => Node::Category { feature, category, left, right },
+            Node::Leaf { .. } => unreachable!("A split is never a leaf"),
+        };
+
+        position
+    }
+
+    // Tries random features until enough of them could split these samples at all, keeping the split with the lowest weighted gini impurity
+    fn best_split(&mut self, indices: &[usize], counts: &[usize], impurity: f64) -> Option<Split> {
+        let mut features: Vec<usize> = (0..self.kinds.len()).collect();
+        let mut best: Option<Split> = None;
+        let mut tried = 0;
+
+        for drawn in 0..features.len() {
+            if tried >= self.features_per_split {
+                break;
+            }
+            let swap_with = drawn + self.rng.below(features.len() - drawn);
+            features.swap(drawn, swap_with);
+            let feature = features[drawn];
+
+            let split = match self.kinds[feature] {
+                FeatureKind::Numeric => self.threshold_split(indices, counts, feature),
+                FeatureKind::Categorical => self.category_split(indices, counts, feature),
+            };
+            let Some(split) = split else {
+                continue;
+            };
+
+            tried += 1;
+            if best.as_ref().is_none_or(|best| split.impurity < best.impurity) {
+                best = Some(split);
+            }
+        }
+
+        best.filter(|best| best.impurity < impurity - 1e-12)
+    }
+
+    fn threshold_split(&self, indices: &[usize], counts: &[usize], feature: usize) -> Option<Split> {
+        let mut values: Vec<(f64, usize)> = indices.iter().map(|index| (self.samples[*index][feature], self.labels[*index])).collect();
+        values.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if values[0].0 == values[values.len() - 1].0 {
+            return None;
+        }
+
+        let mut left_counts = vec![0; self.class_count];
+        let mut best: Option<Split> = None;
+        for position in 0..values.len() - 1 {
+            left_counts[values[position].1] += 1;
+            if values[position].0 == values[position + 1].0 {
+                continue;
+            }
+
+            let impurity = split_impurity(&left_counts, counts, position + 1, values.len());
+            if best.as_ref().is_none_or(|best| impurity < best.impurity) {
+                best = Some(Split {
+                    node: Node::Threshold {
+                        feature,
+                        threshold: (values[position].0 + values[position + 1].0) / 2.0,
+                        left: 0,
+                        right: 0,
+                    },
+                    impurity,
+                });
+            }
+        }
+
+        best
+    }
+
+    fn category_split(&self, indices: &[usize], counts: &[usize], feature: usize) -> Option<Split> {
+        let mut category_counts: HashMap<i64, Vec<usize>> = HashMap::new();
+        for index in indices {
+            category_counts.entry(self.samples[*index][feature] as i64).or_insert_with(|| vec![0; self.class_count])[self.labels[*index]] += 1;
+        }
+        if category_counts.len() < 2 {
+            return None;
+        }
+
+        // Visited in order so ties always go to the same category
+        let mut categories: Vec<(i64, Vec<usize>)> = category_counts.into_iter().collect();
+        categories.sort_by_key(|(category, _)| *category);
+
+        let mut best: Option<Split> = None;
+        for (category, left_counts) in categories {
+            let impurity = split_impurity(&left_counts, counts, left_counts.iter().sum(), indices.len());
+            if best.as_ref().is_none_or(|best| impurity < best.impurity) {
+                best = Some(Split {
+                    node: Node::Category {
+                        feature,
+                        category: category as f64,
+                        left: 0,
+                        right: 0,
+                    },
+                    impurity,
+                });
+            }
+        }
+
+        best
+    }
+
+    fn class_counts(&self, indices: &[usize]) -> Vec<usize> {
+        let mut counts = vec![0; self.class_count];
+        indices.iter().for_each(|index| counts[self.labels[*index]] += 1);
+        counts
+    }
+}
+
+fn goes_left(node: &Node, sample: &[f64]) -> bool {
+    match node {
+        Node::Threshold { feature, threshold, .. } => sample[*feature] <= *threshold,
+        Node::Category { feature, category, .. } => sample[*feature] == *category,
+        Node::Leaf { .. } => false,
+    }
+}
+
+fn gini(counts: &[usize], total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    1.0 - counts.iter().map(|count| (*count as f64 / total as f64).powi(2)).sum::<f64>()
+}
+
+fn split_impurity(left_counts: &[usize], counts: &[usize], left_total: usize, total: usize) -> f64 {
+    let right_counts: Vec<usize> = counts.iter().zip(left_counts).map(|(count, left)| count - left).collect();
+    let right_total = total - left_total;
+    (left_total as f64 * gini(left_counts, left_total) + right_total as f64 * gini(&right_counts, right_total)) / total as f64
+}
+
+// SplitMix64, enough for bootstrap samples and feature draws without pulling in a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(trees: usize) -> TrainingOptions {
+        TrainingOptions {
+            trees,
+            max_depth: None,
+            min_samples_split: 2,
+            seed: 22,
+        }
+    }
+
+    // Class 0 answers fast with status 200, class 1 slowly with status 404, encoded as category 0 and 1
+    fn separable_dataset() -> (Vec<Vec<f64>>, Vec<usize>) {
+        let mut samples = Vec::new();
+        let mut labels = Vec::new();
+        for i in 0..20 {
+            samples.push(vec![i as f64, 0.0]);
+            labels.push(0);
+            samples.push(vec![100.0 + i as f64, 1.0]);
+            labels.push(1);
+        }
+        (samples, labels)
+    }
+
+    #[test]
+    fn separates_a_trivially_separable_dataset() {
+        let (samples, labels) = separable_dataset();
+        let (forest, out_of_bag_accuracy) = Forest::train(&samples, &labels, 2, &[FeatureKind::Numeric, FeatureKind::Categorical], &options(10));
+
+        assert_eq!(forest.trees.len(), 10);
+        assert_eq!(out_of_bag_accuracy, Some(1.0));
+        for (sample, label) in samples.iter().zip(&labels) {
+            assert_eq!(argmax(&forest.probabilities(sample, 2)), *label);
+        }
+        assert_eq!(forest.probabilities(&[5.5, 0.0], 2), vec![1.0, 0.0]);
+        assert_eq!(forest.probabilities(&[150.0, 1.0], 2), vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn separates_on_a_categorical_feature_alone() {
+        let samples: Vec<Vec<f64>> = (0..30).map(|i| vec![(i % 3) as f64]).collect();
+        let labels: Vec<usize> = (0..30).map(|i| i % 3).collect();
+        let (forest, _) = Forest::train(&samples, &labels, 3, &[FeatureKind::Categorical], &options(5));
+
+        for category in 0..3 {
+            assert_eq!(argmax(&forest.probabilities(&[category as f64], 3)), category);
+        }
+    }
+
+    #[test]
+    fn same_seed_trains_the_same_forest() {
+        let (samples, labels) = separable_dataset();
+        let kinds = [FeatureKind::Numeric, FeatureKind::Categorical];
+        let (first, _) = Forest::train(&samples, &labels, 2, &kinds, &options(8));
+        let (second, _) = Forest::train(&samples, &labels, 2, &kinds, &options(8));
+
+        assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&second).unwrap());
+    }
+
+    #[test]
+    fn serialized_forest_predicts_the_same() {
+        let (samples, labels) = separable_dataset();
+        let (forest, _) = Forest::train(&samples, &labels, 2, &[FeatureKind::Numeric, FeatureKind::Categorical], &options(4));
+        let loaded: Forest = serde_json::from_str(&serde_json::to_string(&forest).unwrap()).unwrap();
+
+        for sample in samples.iter().chain([vec![50.0, 0.0], vec![-1.0, -1.0]].iter()) {
+            assert_eq!(loaded.probabilities(sample, 2), forest.probabilities(sample, 2));
+        }
+    }
+}
@@ -0,0 +1,428 @@
+// Random forest that names the framework behind a target from the fuzzer's own results
+//
+// Models are saved as a JSON file:
+//
+//   format            always "wmap-random-forest"
+//   version           MODEL_VERSION, bumped whenever the layout below or the feature vector changes
+//   classes           framework labels, in the order of every probability list in the file
+//   excluded_headers  lowercased headers dropped from the responses before encoding
+//   schema            category tables of the feature vector, each sorted
+//     status_messages, mutation_categories
+//     headers         [{ name, values }] in vocabulary order
+//   forest.trees      [{ nodes }], the root first, each node one of
+//     { "leaf": { probabilities } }
+//     { "threshold": { feature, threshold, left, right } }  left when the feature is at most the threshold
+//     { "category": { feature, category, left, right } }    left when the feature equals the category
+//
+// The feature vector of a response is status_code (0 without a status line), status_message and the bracketed
+// category of the mutation_description, whose rest names the target and would give its label away. Then the
+// diff against the baseline: deviates and status_changed (0 or 1), the number of headers added and removed,
+// body_length_delta and body_similarity, all -1 without a diff except body_length_delta which is 0. Then the
+// timings in microseconds, ttfb_adjusted, total_adjusted and the stddev of the total, all -1 when unknown.
+// Then present (0 or 1), value and capitalization for every header of the schema, its values taken from the
+// normalized response. Categorical features hold an index into their table, or -1 for something not seen in
+// training, capitalization indexes CAPITALIZATIONS.
+
+mod forest;
+
+use crate::features::{self, ResponseFeatures};
+use crate::logger::{self, RequestResult};
+use crate::mutator;
+use crate::signatures::{self, SignatureMatcher};
+use forest::{FeatureKind, Forest, TrainingOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io;
+use std::sync::Mutex;
+
+pub const MODEL_FORMAT: &str = "wmap-random-forest";
+pub const MODEL_VERSION: u32 = 5;
+
+const CAPITALIZATIONS: [&str; 4] = ["lowercase", "uppercase", "titlecase", "other"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HeaderSchema {
+    name: String,
+    values: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Schema {
+    status_messages: Vec<String>,
+    mutation_categories: Vec<String>,
+    headers: Vec<HeaderSchema>,
+}
+
+impl Schema {
+    fn from_records(records: &[ResponseFeatures]) -> Self {
+        let headers: BTreeSet<&str> = records.iter().flat_map(|record| record.headers.iter().map(|(name, _)| name.as_str())).collect();
+
+        Self {
+            status_messages: sorted(records.iter().filter_map(|record| record.status_message.as_deref())),
+            mutation_categories: sorted(records.iter().filter_map(|record| mutator::category(&record.mutation_description))),
+            headers: headers
+                .into_iter()
+                .map(|name| HeaderSchema {
+                    name: name.to_string(),
+                    values: sorted(records.iter().filter_map(|record| record.header(name))),
+                })
+                .collect(),
+        }
+    }
+
+    fn kinds(&self) -> Vec<FeatureKind> {
+        let mut kinds = vec![FeatureKind::Numeric, FeatureKind::Categorical, FeatureKind::Categorical];
+        kinds.extend([FeatureKind::Numeric; 9]);
+        for _ in &self.headers {
+            kinds.extend([FeatureKind::Numeric, FeatureKind::Categorical, FeatureKind::Categorical]);
+        }
+        kinds
+    }
+
+    fn encode(&self, record: &ResponseFeatures) -> Vec<f64> {
+        let mut sample = vec![
+            record.status_code.map_or(0.0, f64::from),
+            category(&self.status_messages, record.status_message.as_deref()),
+            category(&self.mutation_categories, mutator::category(&record.mutation_description)),
+        ];
+
+        let diff = record.baseline_diff.as_ref();
+        sample.extend([
+            diff.map_or(-1.0, |d| d.deviates as u8 as f64),
+            diff.map_or(-1.0, |d| d.status_changed as u8 as f64),
+            diff.map_or(-1.0, |d| d.headers_added.len() as f64),
+            diff.map_or(-1.0, |d| d.headers_removed.len() as f64),
+            diff.map_or(0.0, |d| d.body_length_delta as f64),
+            diff.map_or(-1.0, |d| d.body_similarity),
+        ]);
+
+        let timing = record.timing.as_ref();
+        sample.extend([
+            timing.and_then(|t| t.ttfb_adjusted_us).map_or(-1.0, |us| us as f64),
+            timing.and_then(|t| t.total_adjusted_us).map_or(-1.0, |us| us as f64),
+            timing.and_then(|t| t.total_us.as_ref()).map_or(-1.0, |stats| stats.stddev as f64),
+        ]);
+
+        for header in &self.headers {
+            let value = record.header(&header.name);
+            sample.push(value.is_some() as u8 as f64);
+            sample.push(category(&header.values, value));
+            sample.push(value.map_or(-1.0, |value| {
+                CAPITALIZATIONS
+                    .iter()
+                    .position(|capitalization| *capitalization == features::capitalization(value))
+                    .map_or(-1.0, |position| position as f64)
+            }));
+        }
+
+        sample
+    }
+}
+
+fn sorted<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    values.collect::<BTreeSet<&str>>().into_iter().map(|value| value.to_string()).collect()
+}
+
+fn category(table: &[String], value: Option<&str>) -> f64 {
+    value
+        .and_then(|value| table.binary_search_by(|entry| entry.as_str().cmp(value)).ok())
+        .map_or(-1.0, |position| position as f64)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Model {
+    format: String,
+    version: u32,
+    classes: Vec<String>,
+    excluded_headers: Vec<String>,
+    schema: Schema,
+    forest: Forest,
+}
+
+impl Model {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read model file {}: {}", path, e))?;
+
+        // Checked before the rest so a model from another version gets a clear error instead of a missing field
+        let header: serde_json::Value = serde_json::from_str(&content).map_err(|e| format!("Invalid model file {}: {}", path, e))?;
+        if header.get("format").and_then(|format| format.as_str()) != Some(MODEL_FORMAT) {
+            return Err(format!("Invalid model file {}: not a {} model", path, MODEL_FORMAT));
+        }
+        let version = header.get("version").and_then(|version| version.as_u64());
+        if version != Some(MODEL_VERSION as u64) {
+            return Err(format!(
+                "Unsupported model file {}: version {} but this build reads version {}",
+                path,
+                version.map_or(String::from("unknown"), |version| version.to_string()),
+                MODEL_VERSION
+            ));
+        }
+
+        serde_json::from_value(header).map_err(|e| format!("Invalid model file {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create model file {}: {}", path, e))?;
+        serde_json::to_writer(io::BufWriter::new(file), self).map_err(|e| format!("Failed to write model file {}: {}", path, e))
+    }
+
+    pub fn probabilities(&self, record: &ResponseFeatures) -> Vec<f64> {
+        self.forest.probabilities(&self.schema.encode(record), self.classes.len())
+    }
+
+    // Per-class probabilities of every target, averaged over its responses
+    pub fn report(&self, records: &[ResponseFeatures]) -> String {
+        let mut targets: Vec<(&str, Vec<f64>, usize)> = Vec::new();
+        for record in records {
+            let probabilities = self.probabilities(record);
+            let position = match targets.iter().position(|(url, _, _)| *url == record.url) {
+                Some(position) => position,
+                None => {
+                    targets.push((&record.url, vec![0.0; self.classes.len()], 0));
+                    targets.len() - 1
+                }
+            };
+
+            let (_, totals, count) = &mut targets[position];
+            totals.iter_mut().zip(probabilities).for_each(|(total, probability)| *total += probability);
+            *count += 1;
+        }
+
+        let mut output = String::new();
+        for (url, totals, count) in targets {
+            // Results written before they carried their URL all land in one unnamed target
+            let url = if url.is_empty() { "(unknown target)" } else { url };
+            writeln!(output, "{} ({} responses)", url, count).unwrap();
+
+            let mut classes: Vec<(&String, f64)> = self.classes.iter().zip(totals.iter().map(|total| total / count as f64)).collect();
+            classes.sort_by(|a, b| b.1.total_cmp(&a.1));
+            for (class, probability) in classes {
+                writeln!(output, "  {:<20} {:.3}", class, probability).unwrap();
+            }
+        }
+
+        output
+    }
+}
+
+// Reads a json or jsonl results file, leaving out results without a response and those still throttled after every retry
+pub fn load_results(path: &str, excluded: &[String]) -> Result<Vec<ResponseFeatures>, String> {
+    Ok(logger::read_results(path)?.iter().filter_map(|result| result.features(excluded)).collect())
+}
+
+pub fn train(matches: &clap::ArgMatches) -> Result<(), String> {
+    let excluded: Vec<String> = matches
+        .get_many::<String>("feature-exclude")
+        .map(|names| names.map(|name| name.trim().to_lowercase()).collect())
+        .unwrap_or_default();
+
+    let mut records = Vec::new();
+    for path in matches.get_many::<String>("results").expect("Results files are required") {
+        records.extend(load_results(path, &excluded)?.into_iter().filter(|record| record.framework.is_some()));
+    }
+
+    let classes = sorted(records.iter().filter_map(|record| record.framework.as_deref()));
+    if classes.len() < 2 {
+        return Err(format!(
+            "Training needs responses from at least two frameworks, found {}; label the targets as URL;FRAMEWORK when fuzzing",
+            classes.len()
+        ));
+    }
+
+    let schema = Schema::from_records(&records);
+    let samples: Vec<Vec<f64>> = records.iter().map(|record| schema.encode(record)).collect();
+    let labels: Vec<usize> = records
+        .iter()
+        .map(|record| {
+            classes
+                .iter()
+                .position(|class| Some(class.as_str()) == record.framework.as_deref())
+                .expect("Every framework is a class")
+        })
+        .collect();
+
+    let options = TrainingOptions {
+        trees: *matches.get_one::<usize>("trees").expect("Trees has a default value"),
+        max_depth: matches.get_one::<usize>("max-depth").copied(),
+        min_samples_split: *matches.get_one::<usize>("min-samples-split").expect("Min samples split has a default value"),
+        seed: *matches.get_one::<u64>("seed").expect("Seed has a default value"),
+    };
+    if options.trees == 0 {
+        return Err(String::from("Training needs at least one tree"));
+    }
+    let (forest, out_of_bag_accuracy) = Forest::train(&samples, &labels, classes.len(), &schema.kinds(), &options);
+
+    let model = Model {
+        format: MODEL_FORMAT.to_string(),
+        version: MODEL_VERSION,
+        classes,
+        excluded_headers: excluded,
+        schema,
+        forest,
+    };
+    let path = matches.get_one::<String>("model").expect("Model is required");
+    model.save(path)?;
+
+    println!(
+        "Trained {} trees on {} responses from {} frameworks: {}",
+        options.trees,
+        records.len(),
+        model.classes.len(),
+        model.classes.join(", ")
+    );
+    if let Some(accuracy) = out_of_bag_accuracy {
+        println!("Out-of-bag accuracy: {:.3}", accuracy);
+    }
+    println!("Model written to {}", path);
+
+    Ok(())
+}
+
+pub fn detect(matches: &clap::ArgMatches) -> Result<(), String> {
+    let mut results = Vec::new();
+    for path in matches.get_many::<String>("results").expect("Results files are required") {
+        results.extend(logger::read_results(path)?);
+    }
+
+    let model = matches.get_one::<String>("model").map(|path| Model::load(path)).transpose()?;
+    if let Some(model) = &model {
+        let records: Vec<ResponseFeatures> = results.iter().filter_map(|result| result.features(&model.excluded_headers)).collect();
+        print!("{}", model.report(&records));
+    }
+
+    // The built-in signatures answer when nothing else was asked for
+    let signature_paths: Option<Vec<String>> = matches.get_many::<String>("signatures").map(|paths| paths.cloned().collect());
+    if signature_paths.is_some() || model.is_none() {
+        let signature_matcher = SignatureMatcher::new(signatures::load(&signature_paths.unwrap_or_default())?);
+        results.iter().for_each(|result| signature_matcher.record_stored(result));
+        print!("{}", signature_matcher.report());
+    }
+
+    Ok(())
+}
+
+// Detection right after a fuzzing run, gathering the responses as they come in
+pub struct Detector {
+    model: Model,
+    records: Mutex<Vec<ResponseFeatures>>,
+}
+
+impl Detector {
+    pub fn from_args(matches: &clap::ArgMatches) -> Result<Option<Self>, String> {
+        let Some(path) = matches.get_one::<String>("detect") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            model: Model::load(path)?,
+            records: Mutex::new(Vec::new()),
+        }))
+    }
+
+    pub fn record(&self, result: &RequestResult) {
+        if let Some(features) = ResponseFeatures::from_result(result, &self.model.excluded_headers) {
+            self.records.lock().unwrap().push(features);
+        }
+    }
+
+    pub fn report(self) -> String {
+        let mut records = self.records.into_inner().unwrap();
+        records.sort_by_key(|record| record.request_index);
+        self.model.report(&records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::response::ParsedResponse;
+    use forest::argmax;
+    use std::path::PathBuf;
+
+    fn record(request_index: u32, framework: &str, raw: &str) -> ResponseFeatures {
+        let parsed = ParsedResponse::parse(raw.as_bytes());
+        ResponseFeatures::new(request_index, "http://target/", "[method] GET -> ET", Some(framework), 10, parsed.as_ref(), &[])
+    }
+
+    fn trained_model(records: &[ResponseFeatures]) -> Model {
+        let classes = sorted(records.iter().filter_map(|record| record.framework.as_deref()));
+        let schema = Schema::from_records(records);
+        let samples: Vec<Vec<f64>> = records.iter().map(|record| schema.encode(record)).collect();
+        let labels: Vec<usize> = records
+            .iter()
+            .map(|record| classes.iter().position(|class| Some(class.as_str()) == record.framework.as_deref()).unwrap())
+            .collect();
+        let options = TrainingOptions {
+            trees: 5,
+            max_depth: None,
+            min_samples_split: 2,
+            seed: 22,
+        };
+        let (forest, _) = Forest::train(&samples, &labels, classes.len(), &schema.kinds(), &options);
+
+        Model {
+            format: MODEL_FORMAT.to_string(),
+            version: MODEL_VERSION,
+            classes,
+            excluded_headers: vec![String::from("date")],
+            schema,
+            forest,
+        }
+    }
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wmap-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn mutation_is_encoded_by_its_category_alone() {
+        let raw = b"HTTP/1.1 404 Not Found\r\n\r\n";
+        let parsed = ParsedResponse::parse(raw);
+        let laravel = ResponseFeatures::new(0, "http://laravel.test/app", "[request_target] /app -> /app/", Some("Laravel-10.2"), 10, parsed.as_ref(), &[]);
+        let django = ResponseFeatures::new(0, "http://django.test/", "[request_target] / -> //", Some("Django-4.2"), 10, parsed.as_ref(), &[]);
+        let schema = Schema::from_records(&[laravel, django]);
+
+        assert_eq!(schema.mutation_categories, vec!["request_target"]);
+        let unseen = ResponseFeatures::new(0, "http://other.test/", "[request_target] /other -> /other/", None, 10, parsed.as_ref(), &[]);
+        assert_eq!(schema.encode(&unseen)[2], 0.0);
+    }
+
+    #[test]
+    fn saved_model_loads_back_with_the_same_predictions() {
+        let records: Vec<ResponseFeatures> = (0..10)
+            .flat_map(|i| {
+                [
+                    record(i, "express", "HTTP/1.1 404 Not Found\r\nX-Powered-By: Express\r\n\r\n"),
+                    record(i, "django", "HTTP/1.1 400 Bad Request\r\nx-frame-options: DENY\r\n\r\n"),
+                ]
+            })
+            .collect();
+        let model = trained_model(&records);
+        let path = temporary_path("model-round-trip");
+
+        model.save(path.to_str().unwrap()).unwrap();
+        let loaded = Model::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.classes, vec!["django", "express"]);
+        assert_eq!(loaded.excluded_headers, model.excluded_headers);
+        for record in &records {
+            assert_eq!(loaded.probabilities(record), model.probabilities(record));
+        }
+        assert_eq!(argmax(&loaded.probabilities(&records[0])), 1);
+        assert_eq!(argmax(&loaded.probabilities(&records[1])), 0);
+    }
+
+    #[test]
+    fn model_of_another_version_is_rejected() {
+        let path = temporary_path("model-version");
+        fs::write(&path, format!(r#"{{"format":"{}","version":{}}}"#, MODEL_FORMAT, MODEL_VERSION + 1)).unwrap();
+        let loaded = Model::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.unwrap_err().contains("Unsupported model file"));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::http_client;
+use crate::http_client::response::HttpResponse;
+use crate::logger::{Exchange, RequestResult};
+use crate::rate_limiter::{self, RateLimiter};
+use crate::timing::TimingSummary;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::iter::Peekable;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+pub struct PlannedRequest {
+    pub request_index: u32,
+    pub mutation_description: String,
+    pub url: String,
+    // Position of the target in the input, which may list the same URL twice
+    pub target_index: usize,
+    // The method the mutation was derived from, which may differ from the one in the request line
+    pub method: String,
+    pub request: Vec<u8>,
+    pub framework: Option<String>,
+    // Timing-sensitive mutations are sent on their own, once the concurrent ones have finished
+    pub sequential: bool,
+    // The clean request of its target and method, which the mutated responses are diffed against
+    pub baseline: bool,
+}
+
+// What the checkpoint, the baselines and the filters need to know about a planned request, without its bytes
+pub struct RequestKey {
+    pub request_index: u32,
+    pub url: String,
+    pub target_index: usize,
+    pub method: String,
+    pub mutation_description: String,
+    pub baseline: bool,
+}
+
+impl From<&PlannedRequest> for RequestKey {
+    fn from(planned: &PlannedRequest) -> Self {
+        Self {
+            request_index: planned.request_index,
+            url: planned.url.clone(),
+            target_index: planned.target_index,
+            method: planned.method.clone(),
+            mutation_description: planned.mutation_description.clone(),
+            baseline: planned.baseline,
+        }
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// The first SIGINT/SIGTERM lets in-flight requests finish and stops sending new ones, a second one exits right away
+pub fn handle_interruptions() {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        eprintln!("Interrupted, waiting for in-flight requests to finish (press Ctrl-C again to quit now)");
+    })
+    .expect("Failed to set the interruption handler");
+}
+
+struct Scheduler<I: Iterator<Item = PlannedRequest>> {
+    // Requests are crafted lazily and only pulled into the queue once no queued one can be sent
+    planned_requests: Peekable<I>,
+    // Each request keeps its position in the queue so results can be put back in that order
+    queue: VecDeque<(usize, PlannedRequest)>,
+    next_position: usize,
+    // Position of the first request of every target pulled in, from the one holding the oldest unwritten result on
+    target_starts: VecDeque<usize>,
+    last_url: Option<String>,
+    written: usize,
+    max_open_targets: usize,
+    in_flight_per_target: HashMap<(String, u16), usize>,
+}
+
+impl<I: Iterator<Item = PlannedRequest>> Scheduler<I> {
+    // Queues the next request unless it starts a target too far ahead of the oldest unwritten result. A slow request
+    // holds back the results after it, so this bounds them to those of a few targets rather than the rest of the run
+    fn pull(&mut self) -> bool {
+        while self.target_starts.len() > 1 && self.target_starts[1] <= self.written {
+            self.target_starts.pop_front();
+        }
+
+        let Some(planned) = self.planned_requests.peek() else {
+            return false;
+        };
+        if self.last_url.as_ref() != Some(&planned.url) {
+            if self.target_starts.len() >= self.max_open_targets {
+                return false;
+            }
+            self.target_starts.push_back(self.next_position);
+            self.last_url = Some(planned.url.clone());
+        }
+
+        let planned = self.planned_requests.next().expect("A request was peeked");
+        self.queue.push_back((self.next_position, planned));
+        self.next_position += 1;
+        true
+    }
+}
+
+// Holds results that completed ahead of earlier ones, so only out-of-order results are ever buffered
+struct Reorder {
+    next_position: usize,
+    pending: BTreeMap<usize, RequestResult>,
+}
+
+// Sends every request and hands each result to `on_result` in the order of `planned_requests`, as soon as all those
+// before it are done. Targets are expected one after the other, as the requests of each one are planned together
+pub fn execute(
+    planned_requests: impl Iterator<Item = PlannedRequest> + Send,
+    concurrency: usize,
+    per_target_concurrency: usize,
+    rate_limiter: &RateLimiter,
+    samples: usize,
+    on_result: &(dyn Fn(RequestResult) + Sync),
+) {
+    let concurrency = concurrency.max(1);
+    let per_target_concurrency = per_target_concurrency.max(1);
+    let scheduler = Mutex::new(Scheduler {
+        planned_requests: planned_requests.peekable(),
+        queue: VecDeque::new(),
+        next_position: 0,
+        target_starts: VecDeque::new(),
+        last_url: None,
+        written: 0,
+        // Enough targets to keep every worker busy, and one more to move on to while the oldest finishes
+        max_open_targets: concurrency.div_ceil(per_target_concurrency) + 1,
+        in_flight_per_target: HashMap::new(),
+    });
+    let slot_released = Condvar::new();
+    let reorder = Mutex::new(Reorder {
+        next_position: 0,
+        pending: BTreeMap::new(),
+    });
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency {
+            scope.spawn(|| {
+                while let Some((position, planned)) = next_request(&scheduler, &slot_released, per_target_concurrency) {
+                    let target = target_of(&planned.url);
+                    let result = send(planned, rate_limiter, samples);
+
+                    let mut state = scheduler.lock().unwrap();
+                    if let Some(in_flight) = state.in_flight_per_target.get_mut(&target) {
+                        *in_flight -= 1;
+                    }
+                    slot_released.notify_all();
+                    drop(state);
+
+                    // Completion order varies between runs, the output order must not
+                    let written = {
+                        let reorder = &mut *reorder.lock().unwrap();
+                        reorder.pending.insert(position, result);
+                        while let Some(result) = reorder.pending.remove(&reorder.next_position) {
+                            on_result(result);
+                            reorder.next_position += 1;
+                        }
+                        reorder.next_position
+                    };
+
+                    let mut state = scheduler.lock().unwrap();
+                    state.written = state.written.max(written);
+                    slot_released.notify_all();
+                }
+            });
+        }
+    });
+
+    // After an interrupt, results done behind a request that never ran would otherwise be lost
+    for result in reorder.into_inner().unwrap().pending.into_values() {
+        on_result(result);
+    }
+}
+
+// Emits every crafted request as a result without any network I/O
+pub fn dry_run(planned_requests: impl Iterator<Item = PlannedRequest>, on_result: &(dyn Fn(RequestResult) + Sync)) {
+    for planned in planned_requests {
+        on_result(into_result(planned, None));
+    }
+}
+
+// Takes the first queued request whose target still has a free slot, waiting for one if needed
+fn next_request<I: Iterator<Item = PlannedRequest>>(scheduler: &Mutex<Scheduler<I>>, slot_released: &Condvar, per_target_concurrency: usize) -> Option<(usize, PlannedRequest)> {
+    let mut state = scheduler.lock().unwrap();
+
+    loop {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let position = state
+            .queue
+            .iter()
+            .position(|(_, planned)| state.in_flight_per_target.get(&target_of(&planned.url)).copied().unwrap_or(0) < per_target_concurrency);
+
+        if let Some(position) = position {
+            let (queue_position, planned) = state.queue.remove(position).unwrap();
+            *state.in_flight_per_target.entry(target_of(&planned.url)).or_insert(0) += 1;
+            return Some((queue_position, planned));
+        }
+
+        if state.pull() {
+            continue;
+        }
+        if state.queue.is_empty() && state.planned_requests.peek().is_none() {
+            return None;
+        }
+
+        state = slot_released.wait(state).unwrap();
+    }
+}
+
+fn target_of(url: &str) -> (String, u16) {
+    let (host, port, _) = http_client::parse_url(url);
+    (host, port)
+}
+
+// Sends the request `samples` times. Only the first response is kept, the others add their timings
+fn send(planned: PlannedRequest, rate_limiter: &RateLimiter, samples: usize) -> RequestResult {
+    let (response, throttled) = send_once(&planned, rate_limiter);
+    let mut timings = vec![response.timing];
+    for _ in 1..samples {
+        timings.push(send_once(&planned, rate_limiter).0.timing);
+    }
+
+    let exchange = Exchange {
+        response: String::from_utf8_lossy(&response.raw).to_string(),
+        response_base64: BASE64_STANDARD.encode(&response.raw),
+        // Filled in once the result comes out of the executor
+        normalized_response: None,
+        parsed_response: response.parsed,
+        response_time: response.response_time,
+        timing: TimingSummary::from_samples(&timings),
+        outcome: response.outcome,
+        throttled,
+    };
+
+    into_result(planned, Some(exchange))
+}
+
+fn send_once(planned: &PlannedRequest, rate_limiter: &RateLimiter) -> (HttpResponse, bool) {
+    let target = target_of(&planned.url);
+    let mut retries = 0;
+
+    // Throttled responses say nothing about the framework, so retry them once the host allows it
+    loop {
+        rate_limiter.acquire(&target);
+        let response = http_client::send_request(&planned.url, &planned.request);
+
+        match response.parsed.as_ref().filter(|parsed| rate_limiter::is_throttled(parsed)) {
+            Some(parsed) => {
+                rate_limiter.report_throttled(&target, rate_limiter::retry_after(parsed));
+                if retries >= rate_limiter.max_retries {
+                    return (response, true);
+                }
+                retries += 1;
+            }
+            None => {
+                rate_limiter.report_success(&target);
+                return (response, false);
+            }
+        }
+    }
+}
+
+fn into_result(planned: PlannedRequest, exchange: Option<Exchange>) -> RequestResult {
+    RequestResult {
+        request_index: planned.request_index,
+        url: planned.url,
+        mutation_description: planned.mutation_description,
+        // Requests may hold any bytes, so the display string is lossy and the base64 copy is exact
+        request: String::from_utf8_lossy(&planned.request).to_string(),
+        request_base64: BASE64_STANDARD.encode(&planned.request),
+        exchange,
+        baseline_diff: None,
+        framework: planned.framework,
+    }
+}
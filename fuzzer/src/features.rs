@@ -0,0 +1,290 @@
+use crate::baseline::BaselineDiff;
+use crate::http_client::response::ParsedResponse;
+use crate::logger::{escape_csv_value, RequestResult};
+use crate::timing::TimingSummary;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+// What the classifiers need from one response, kept instead of the raw response
+pub struct ResponseFeatures {
+    pub request_index: u32,
+    pub url: String,
+    pub mutation_description: String,
+    pub framework: Option<String>,
+    pub response_time: u128,
+    pub status_code: Option<u16>,
+    pub status_message: Option<String>,
+    // Lowercased names in order of first appearance, a repeated header keeps its last value
+    pub headers: Vec<(String, String)>,
+    pub baseline_diff: Option<BaselineDiff>,
+    pub timing: Option<TimingSummary>,
+}
+
+impl ResponseFeatures {
+    // A response still throttled after every retry is the rate limiter's, not the framework's
+    pub fn from_result(result: &RequestResult, excluded: &[String]) -> Option<Self> {
+        let exchange = result.exchange.as_ref().filter(|exchange| !exchange.throttled)?;
+
+        Some(Self {
+            baseline_diff: result.baseline_diff.clone(),
+            timing: Some(exchange.timing.clone()),
+            ..Self::new(
+                result.request_index,
+                &result.url,
+                &result.mutation_description,
+                result.framework.as_deref(),
+                exchange.response_time,
+                normalized_head(exchange.normalized_response.as_deref(), exchange.parsed_response.as_ref()).as_ref(),
+                excluded,
+            )
+        })
+    }
+
+    pub fn new(request_index: u32, url: &str, mutation_description: &str, framework: Option<&str>, response_time: u128, parsed: Option<&ParsedResponse>, excluded: &[String]) -> Self {
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for (name, value) in parsed.map(|p| p.head.headers.as_slice()).unwrap_or_default() {
+            let name = name.trim().to_lowercase();
+            if excluded.contains(&name) {
+                continue;
+            }
+            match headers.iter_mut().find(|(existing, _)| *existing == name) {
+                Some((_, existing_value)) => *existing_value = value.clone(),
+                None => headers.push((name, value.clone())),
+            }
+        }
+
+        let status_code = parsed.and_then(|p| p.head.status_code);
+        Self {
+            request_index,
+            url: url.to_string(),
+            mutation_description: mutation_description.to_string(),
+            framework: framework.map(|f| f.to_string()),
+            response_time,
+            status_code,
+            status_message: status_code.and(parsed.map(|p| p.head.reason_phrase.clone())),
+            headers,
+            baseline_diff: None,
+            timing: None,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+}
+
+// Header values come from the normalized response when there is one, so a date or session ID is a single value
+pub fn normalized_head(normalized_response: Option<&str>, parsed: Option<&ParsedResponse>) -> Option<ParsedResponse> {
+    normalized_response.and_then(|normalized| ParsedResponse::parse(normalized.as_bytes())).or_else(|| parsed.cloned())
+}
+
+// Feature vectors with the same schema the random forest and neural network classifiers build
+pub struct FeatureExport {
+    path: String,
+    format: String,
+    vocabulary: Option<Vec<String>>,
+    excluded: Vec<String>,
+    records: Mutex<Vec<ResponseFeatures>>,
+}
+
+impl FeatureExport {
+    pub fn new(path: &str, format: &str, vocabulary: Option<Vec<String>>, excluded: Vec<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            format: format.to_string(),
+            vocabulary: vocabulary.map(|names| names.iter().map(|name| name.trim().to_lowercase()).collect()),
+            excluded: excluded.iter().map(|name| name.trim().to_lowercase()).collect(),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn from_args(matches: &clap::ArgMatches) -> Option<Self> {
+        let path = matches.get_one::<String>("features")?;
+
+        Some(Self::new(
+            path,
+            matches.get_one::<String>("features-format").expect("Features format has a default value"),
+            matches.get_many::<String>("feature-headers").map(|names| names.cloned().collect()),
+            matches.get_many::<String>("feature-exclude").map(|names| names.cloned().collect()).unwrap_or_default(),
+        ))
+    }
+
+    pub fn record(&self, result: &RequestResult) {
+        if let Some(features) = ResponseFeatures::from_result(result, &self.excluded) {
+            self.records.lock().unwrap().push(features);
+        }
+    }
+
+    pub fn write(self) {
+        let mut records = self.records.into_inner().unwrap();
+        records.sort_by_key(|record| record.request_index);
+
+        // Without a fixed vocabulary every header seen in this run becomes a column, sorted like the classifiers do
+        let vocabulary = self.vocabulary.unwrap_or_else(|| {
+            records
+                .iter()
+                .flat_map(|record| record.headers.iter().map(|(name, _)| name.clone()))
+                .collect::<BTreeSet<String>>()
+                .into_iter()
+                .collect()
+        });
+
+        let columns = columns(&vocabulary);
+        let rows: Vec<Vec<Value>> = records.iter().map(|record| row(record, &vocabulary)).collect();
+
+        let file = File::create(&self.path).expect("Failed to create features file");
+        let mut writer = io::BufWriter::new(file);
+        match self.format.as_str() {
+            "json" => write_json(&mut writer, &columns, rows),
+            _ => write_csv(&mut writer, &columns, &rows),
+        }
+        .expect("Failed to write features file");
+    }
+}
+
+fn columns(vocabulary: &[String]) -> Vec<String> {
+    let mut columns: Vec<String> = ["request_index", "mutation_description", "framework", "response_time", "status_code", "status_message"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+
+    columns.extend(vocabulary.iter().map(|header| format!("{}_present", header)));
+    // Vocabulary index of each response header in order, padded with -1
+    columns.extend((0..vocabulary.len()).map(|position| format!("header_order_{}", position)));
+    for header in vocabulary {
+        columns.push(format!("{}_value", header));
+        columns.push(format!("{}_capitalization", header));
+    }
+    columns.extend(
+        [
+            "baseline_deviates",
+            "baseline_status_changed",
+            "baseline_headers_added",
+            "baseline_headers_removed",
+            "baseline_body_length_delta",
+            "baseline_body_similarity",
+            "connect_median_us",
+            "tcp_connect_median_us",
+            "ttfb_median_us",
+            "total_median_us",
+            "total_p90_us",
+            "total_stddev_us",
+            "network_baseline_us",
+            "ttfb_adjusted_us",
+            "total_adjusted_us",
+        ]
+        .iter()
+        .map(|column| column.to_string()),
+    );
+
+    columns
+}
+
+fn row(record: &ResponseFeatures, vocabulary: &[String]) -> Vec<Value> {
+    let mut row = vec![
+        Value::from(record.request_index),
+        Value::from(record.mutation_description.clone()),
+        record.framework.clone().map_or(Value::Null, Value::from),
+        Value::from(record.response_time as u64),
+        record.status_code.map_or(Value::Null, Value::from),
+        record.status_message.clone().map_or(Value::Null, Value::from),
+    ];
+
+    row.extend(vocabulary.iter().map(|header| Value::from(record.header(header).is_some() as u8)));
+
+    let mut header_order: Vec<i64> = record
+        .headers
+        .iter()
+        .map(|(name, _)| vocabulary.iter().position(|header| header == name).map_or(-1, |position| position as i64))
+        .collect();
+    header_order.resize(vocabulary.len(), -1);
+    row.extend(header_order.into_iter().map(Value::from));
+
+    for header in vocabulary {
+        let value = record.header(header);
+        row.push(Value::from(value.unwrap_or("unknown")));
+        row.push(Value::from(value.map_or("other", capitalization)));
+    }
+
+    // Empty for baselines and for responses that could not be compared
+    let diff = record.baseline_diff.as_ref();
+    row.push(diff.map_or(Value::Null, |d| Value::from(d.deviates as u8)));
+    row.push(diff.map_or(Value::Null, |d| Value::from(d.status_changed as u8)));
+    row.push(diff.map_or(Value::Null, |d| Value::from(d.headers_added.join(" "))));
+    row.push(diff.map_or(Value::Null, |d| Value::from(d.headers_removed.join(" "))));
+    row.push(diff.map_or(Value::Null, |d| Value::from(d.body_length_delta)));
+    row.push(diff.map_or(Value::Null, |d| Value::from(d.body_similarity)));
+
+    let timing = record.timing.as_ref();
+    row.push(timing.and_then(|t| t.connect_us.as_ref()).map_or(Value::Null, |stats| Value::from(stats.median)));
+    row.push(timing.and_then(|t| t.tcp_connect_us.as_ref()).map_or(Value::Null, |stats| Value::from(stats.median)));
+    row.push(timing.and_then(|t| t.ttfb_us.as_ref()).map_or(Value::Null, |stats| Value::from(stats.median)));
+    row.push(timing.and_then(|t| t.total_us.as_ref()).map_or(Value::Null, |stats| Value::from(stats.median)));
+    row.push(timing.and_then(|t| t.total_us.as_ref()).map_or(Value::Null, |stats| Value::from(stats.p90)));
+    row.push(timing.and_then(|t| t.total_us.as_ref()).map_or(Value::Null, |stats| Value::from(stats.stddev)));
+    row.push(timing.and_then(|t| t.network_baseline_us).map_or(Value::Null, Value::from));
+    row.push(timing.and_then(|t| t.ttfb_adjusted_us).map_or(Value::Null, Value::from));
+    row.push(timing.and_then(|t| t.total_adjusted_us).map_or(Value::Null, Value::from));
+
+    row
+}
+
+// Same classes as Python's str.islower(), str.isupper() and str.istitle()
+pub fn capitalization(value: &str) -> &'static str {
+    let cased: Vec<char> = value.chars().filter(|c| c.is_lowercase() || c.is_uppercase()).collect();
+
+    if !cased.is_empty() && cased.iter().all(|c| c.is_lowercase()) {
+        return "lowercase";
+    }
+    if !cased.is_empty() && cased.iter().all(|c| c.is_uppercase()) {
+        return "uppercase";
+    }
+
+    // Title case: uppercase only at the start of a word, lowercase only inside one
+    let mut previous_cased = false;
+    let mut titlecase = !cased.is_empty();
+    for c in value.chars() {
+        if c.is_uppercase() {
+            titlecase &= !previous_cased;
+            previous_cased = true;
+        } else if c.is_lowercase() {
+            titlecase &= previous_cased;
+            previous_cased = true;
+        } else {
+            previous_cased = false;
+        }
+    }
+
+    if titlecase {
+        "titlecase"
+    } else {
+        "other"
+    }
+}
+
+fn write_csv(writer: &mut impl Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+    writeln!(writer, "{}", columns.iter().map(|column| escape_csv_value(column)).collect::<Vec<String>>().join(","))?;
+
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                Value::String(text) => escape_csv_value(text),
+                other => other.to_string(),
+            })
+            .collect();
+        writeln!(writer, "{}", cells.join(","))?;
+    }
+
+    Ok(())
+}
+
+fn write_json(writer: &mut impl Write, columns: &[String], rows: Vec<Vec<Value>>) -> io::Result<()> {
+    let objects: Vec<Map<String, Value>> = rows.into_iter().map(|row| columns.iter().cloned().zip(row).collect()).collect();
+    serde_json::to_writer(&mut *writer, &objects)?;
+    writeln!(writer)
+}
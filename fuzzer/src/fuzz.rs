@@ -1,15 +1,21 @@
 use crate::http_client;
-use std::collections::BTreeMap;
+use crate::http_client::headers::Headers;
+use crate::mutator::definitions::{Field, MutationDefinition};
+use std::sync::Arc;
 use url::Url;
 
+// Method, request target and headers of a request carrying raw bytes in one of them
+pub type RawRequestParts = (Vec<u8>, Vec<u8>, Headers);
+
 pub struct Fuzzer {
     pub methods: Vec<String>,
     pub request_index: u32,
+    definitions: Arc<Vec<MutationDefinition>>,
 }
 
 impl Fuzzer {
-    pub fn new(methods: Vec<String>, request_index: u32) -> Self {
-        Self { methods, request_index }
+    pub fn new(methods: Vec<String>, request_index: u32, definitions: Arc<Vec<MutationDefinition>>) -> Self {
+        Self { methods, request_index, definitions }
     }
 
     pub fn fuzz_http_method(&self, method: &str) -> (Vec<String>, Vec<String>) {
@@ -35,60 +41,10 @@ impl Fuzzer {
             }
         }
 
-        // 3. Add chars
-        // Common chars
-        let common_chars = vec!['A', 'B'];
-        let added_vec = self.add_chars(method, common_chars);
-        for added in added_vec {
-            mutated_methods_descriptions.push(format!("[method] {} -> {}", method, added));
-            mutated_methods.push(added);
-        }
-
-        // Unexpected chars
-        let unexpected_chars = vec!['$', '@', '!', '1'];
-        let added_vec = self.add_chars(method, unexpected_chars);
-        for added in added_vec {
-            mutated_methods_descriptions.push(format!("[method] {} -> {}", method, added));
-            mutated_methods.push(added);
-        }
-
-        // Large number of chars
-        let large_strings = vec!["#".repeat(1024), "%".repeat(4096)];
-        let added_vec = self.add_chars(method, large_strings);
-        for added in added_vec {
-            mutated_methods_descriptions.push(format!("[method] {} -> {}", method, added));
-            mutated_methods.push(added);
-        }
-
-        // 4. Modify existing chars
-        let replacements = vec![('O', "0"), ('E', "3"), ('A', "@"), ('P', "Ρ"), ('T', "✝")];
-        let replaced_vec = self.replace_chars(method, &replacements);
-        for replaced in replaced_vec {
-            mutated_methods_descriptions.push(format!("[method] {} -> {}", method, replaced));
-            mutated_methods.push(replaced);
-        }
-
-        // 5. Case variations
-        // Lowercase
-        mutated_methods_descriptions.push(format!("[method] {} -> {}", method, method.to_lowercase()));
-        mutated_methods.push(method.to_lowercase());
-
-        // Toggle between upper and lower case letters
-        let case_alternated = self.alternate_case(method);
-        mutated_methods_descriptions.push(format!("[method] {} -> {}", method, case_alternated));
-        mutated_methods.push(case_alternated);
-
-        // 6. Whitespace and formatting
-        let with_spaces = self.add_whitespaces_between_chars(method);
-        mutated_methods_descriptions.push(format!("[method] {} -> {}", method, with_spaces));
-        mutated_methods.push(with_spaces);
-
-        // 7. Concatenated methods
-        let appended_methods = vec!["POST", "GET"];
-        let concatenated_vec = self.concatenate_with_methods(method, &appended_methods);
-        for concatenated in concatenated_vec {
-            mutated_methods_descriptions.push(format!("[method] {} -> {}", method, concatenated));
-            mutated_methods.push(concatenated);
+        // 3. Added, substituted and concatenated chars, case and whitespace variations from the mutation definitions
+        for mutated in self.apply_definitions(Field::Method, method) {
+            mutated_methods_descriptions.push(format!("[method] {} -> {}", method, mutated));
+            mutated_methods.push(mutated);
         }
 
         (mutated_methods_descriptions, mutated_methods)
@@ -108,312 +64,452 @@ impl Fuzzer {
         }
 
         // Interchange chars
-        if let Some(r) = resource {
+        if let Some(r) = &resource {
             let len = r.len();
             if len >= 2 {
-                let swapped_resource = self.swap_chars(&r, 0, len - 1);
+                let swapped_resource = self.swap_chars(r, 0, len - 1);
                 let swapped = format!("{}/{}", path, swapped_resource);
                 mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, swapped));
                 mutated_request_targets.push(swapped);
             }
         }
 
-        // Add chars
-        let chars = vec!['/', '#', '?', '$', '%'];
-        for char in chars {
-            let added = format!("{}{}", request_target, char);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, added));
-            mutated_request_targets.push(added);
+        // 2. Added chars, path, query, fragment and encoding mutations from the mutation definitions
+        let (path_before_query, query) = request_target.split_once('?').unwrap_or((request_target, ""));
+        let fragment = request_target.split_once('#');
+
+        for definition in self.definitions.iter() {
+            let mutated_targets = match definition.field {
+                Field::RequestTarget => definition.apply(request_target, &[]),
+                Field::LastSegment => definition
+                    .apply(resource.as_deref().unwrap_or(""), &[])
+                    .into_iter()
+                    .map(|segment| format!("{}/{}", path, segment))
+                    .collect(),
+                Field::Query if !query.is_empty() => definition
+                    .apply(query, &[("{query}", query)])
+                    .into_iter()
+                    .map(|mutated_query| format!("{}?{}", path_before_query, mutated_query))
+                    .collect(),
+                Field::Fragment => fragment.map_or(Vec::new(), |(path_before_fragment, fragment)| {
+                    definition
+                        .apply(fragment, &[])
+                        .into_iter()
+                        .map(|mutated_fragment| format!("{}#{}", path_before_fragment, mutated_fragment))
+                        .collect()
+                }),
+                _ => Vec::new(),
+            };
+
+            for mutated in mutated_targets {
+                mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, mutated));
+                mutated_request_targets.push(mutated);
+            }
         }
 
-        // 2. Path manipulation
-        // Alter path separator
-        let altered = request_target.replace("/", "\\");
-        mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, altered));
-        mutated_request_targets.push(altered);
-
-        // Path traversal sequences
-        let mut path_traversal = format!("{}{}", request_target, "/../");
-        mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, path_traversal));
-        mutated_request_targets.push(path_traversal);
-
-        path_traversal = format!("{}{}", request_target, "/../../../../../../../../../../../../../../../../");
-        mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, path_traversal));
-        mutated_request_targets.push(path_traversal);
-
-        // Overlong segments
-        let overlong_segment = format!("{}/{}", path, "too-long-".repeat(50));
-        mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, overlong_segment));
-        mutated_request_targets.push(overlong_segment);
-
-        // Slash padding
-        let slash_padding = request_target.replace("/", "////");
-        mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, slash_padding));
-        mutated_request_targets.push(slash_padding);
-
-        // 3. Query string mutations
-        let (path, query) = request_target.split_once('?').unwrap_or((request_target, ""));
-        if !query.is_empty() {
-            // Duplicate query parameter
-            let duplicated = format!("{}?{}&{}", path, query, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, duplicated));
-            mutated_request_targets.push(duplicated);
-
-            // Add unexpected query parameter
-            let unexpected = format!("{}?{}&unexpected=1", path, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, unexpected));
-            mutated_request_targets.push(unexpected);
-
-            // Empty query parameter
-            let empty = format!("{}?{}&param=", path, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, empty));
-            mutated_request_targets.push(empty);
-
-            // Large query parameter values
-            let long = format!("{}?{}{}", path, query, "a".repeat(1024));
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, long));
-            mutated_request_targets.push(long);
-
-            // Special characters in query parameters
-            let mut special_chars = format!("{}?{}!", path, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, special_chars));
-            mutated_request_targets.push(special_chars);
-
-            special_chars = format!("{}?{}&param=<script>alert(1)</script>", path, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, special_chars));
-            mutated_request_targets.push(special_chars);
-
-            // Malformed query string
-            let mut malformed = format!("{}?{}?otherparam=othervalue", path, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, malformed));
-            mutated_request_targets.push(malformed);
-
-            malformed = format!("{}?{}&", path, query);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, malformed));
-            mutated_request_targets.push(malformed);
+        (mutated_requests_targets_descriptions, mutated_request_targets)
+    }
+
+    pub fn fuzz_http_version(&self, http_version: &str) -> (Vec<String>, Vec<String>) {
+        let mut mutated_versions_descriptions = Vec::new();
+        let mut mutated_versions = Vec::new();
+
+        // Uncommon, malformed, overlong and encoded versions all come from the mutation definitions
+        for mutated in self.apply_definitions(Field::HttpVersion, http_version) {
+            mutated_versions_descriptions.push(format!("[http version] {} -> {}", http_version, mutated));
+            mutated_versions.push(mutated);
         }
 
-        // 4. Fragment mutations
-        if let Some((path, _fragment)) = request_target.split_once('#') {
-            // Alter or add fragment
-            let mut fragment_mutation = format!("{}#fragme@nt", path);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, fragment_mutation));
-            mutated_request_targets.push(fragment_mutation);
+        (mutated_versions_descriptions, mutated_versions)
+    }
+
+    pub fn fuzz_headers(&self, base_url: &str) -> (Vec<String>, Vec<Headers>) {
+        let normalized_url = http_client::normalize_url(base_url);
+        let parsed_url = Url::parse(&normalized_url).expect("Invalid URL format");
+        let domain = parsed_url.host_str().unwrap_or("");
+
+        let base_headers = http_client::get_default_headers(domain);
+
+        let mut mutated_headers_descriptions = Vec::new();
+        let mut mutated_headers = Vec::new();
 
-            fragment_mutation = format!("{}#", path);
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, fragment_mutation));
-            mutated_request_targets.push(fragment_mutation);
+        // 1. Header values from the mutation definitions
+        for definition in self.definitions.iter().filter(|definition| definition.field == Field::Header) {
+            let name = definition.header.as_deref().unwrap_or_default();
+            let original = base_headers.get(name).unwrap_or("");
 
-            fragment_mutation = format!("{}#{}", path, "longfragment".repeat(1024));
-            mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, fragment_mutation));
-            mutated_request_targets.push(fragment_mutation);
+            for mutated in definition.apply(original, &[("{domain}", domain), ("{url}", &normalized_url)]) {
+                let mut headers = base_headers.clone();
+                headers.set(name, &mutated);
+                mutated_headers_descriptions.push(format!("[header] {}:{} -> {}:{}", name, original, name, mutated));
+                mutated_headers.push(headers);
+            }
         }
 
-        // 5. Encoding and escaping mutations
-        // Slash encoding
-        let slash_encoding = request_target.replace("/", "%2F");
-        mutated_requests_targets_descriptions.push(format!("[request_target] {} -> {}", request_target, slash_encoding));
-        mutated_request_targets.push(slash_encoding);
+        // 2. Header order and duplicates
+        mutated_headers_descriptions.push(String::from("[header] Reversed header order"));
+        mutated_headers.push(base_headers.reversed());
 
-        (mutated_requests_targets_descriptions, mutated_request_targets)
+        let mut headers = base_headers.clone();
+        headers.append("Host", domain);
+        mutated_headers_descriptions.push(format!("[header] Host:{} -> Host:{}, Host:{}", domain, domain, domain));
+        mutated_headers.push(headers);
+
+        (mutated_headers_descriptions, mutated_headers)
     }
 
-    pub fn fuzz_http_version(&self, http_version: &str) -> (Vec<String>, Vec<String>) {
-        let mut mutated_versions_descriptions = Vec::new();
-        let mut mutated_versions = Vec::new();
+    pub fn fuzz_header_names(&self, base_url: &str) -> (Vec<String>, Vec<Headers>) {
+        let normalized_url = http_client::normalize_url(base_url);
+        let parsed_url = Url::parse(&normalized_url).expect("Invalid URL format");
+        let domain = parsed_url.host_str().unwrap_or("");
 
-        // 1. Valid but uncommon versions
-        let uncommon_versions = vec!["HTTP/0.9", "HTTP/2.0", "HTTP/3.0"];
-        for v in uncommon_versions {
-            mutated_versions_descriptions.push(format!("[http version] {} -> {}", http_version, v.to_string()));
-            mutated_versions.push(v.to_string());
-        }
+        let base_headers = http_client::get_default_headers(domain);
 
-        // 2. Malformed versions
-        let malformed_versions = vec![
-            "HTTP/1.",    // Incomplete version
-            "HTTP/1.2.3", // Extra dot
-            "HTT/1.1",    // Typo in protocol
-            "HTTP/1.1 ",  // Trailing space
-            "HTTP/",      // Empty version number
-        ];
-        for v in malformed_versions {
-            mutated_versions_descriptions.push(format!("[http version] {} -> {}", http_version, v.to_string()));
-            mutated_versions.push(v.to_string());
-        }
+        let mut mutated_header_names_descriptions = Vec::new();
+        let mut mutated_header_names = Vec::new();
+
+        let names = vec!["Host", "User-Agent", "Content-Type", "X-Forwarded-For"];
+
+        for name in names {
+            let value = base_headers.get(name).unwrap_or("");
+
+            // 1. Case variations
+            let mut renamed_names = vec![name.to_lowercase(), name.to_uppercase(), self.alternate_case(name)];
+
+            // 2. Underscore instead of dash
+            if name.contains('-') {
+                renamed_names.push(name.replace('-', "_"));
+            }
+
+            // 3. Non-token chars
+            let non_token_chars = vec!['@', '(', '"'];
+            renamed_names.extend(self.add_chars(name, non_token_chars));
 
-        // 3. Unexpected characters
-        let unexpected_char_versions = vec!["HTTP/1.1#", "HTTP/1.1!", "HTTP/1.1@"];
-        for v in unexpected_char_versions {
-            mutated_versions_descriptions.push(format!("[http version] {} -> {}", http_version, v.to_string()));
-            mutated_versions.push(v.to_string());
+            for renamed in renamed_names {
+                let mut headers = base_headers.clone();
+                headers.rename(name, &renamed);
+                mutated_header_names_descriptions.push(format!("[header name] {} -> {}", name, renamed));
+                mutated_header_names.push(headers);
+            }
+
+            // 4. Space before the colon
+            let mut headers = base_headers.clone();
+            headers.set_raw(name, &format!("{} : {}", name, value));
+            mutated_header_names_descriptions.push(format!("[header name] {}: -> {} :", name, name));
+            mutated_header_names.push(headers);
+
+            // 5. Missing colon
+            let mut headers = base_headers.clone();
+            headers.set_raw(name, &format!("{} {}", name, value));
+            mutated_header_names_descriptions.push(format!("[header name] {}: -> {}", name, name));
+            mutated_header_names.push(headers);
         }
 
-        // 4. Overlong version
-        let overlong_version = format!("HTTP/1.1{}", "A".repeat(1024));
-        mutated_versions_descriptions.push(format!("[http version] {} -> {}", http_version, overlong_version));
-        mutated_versions.push(overlong_version);
+        // 6. Very long names
+        for length in [1024, 8192] {
+            let long_name = format!("X-{}", "A".repeat(length));
+            let mut headers = base_headers.clone();
+            headers.append(&long_name, "1");
+            mutated_header_names_descriptions.push(format!("[header name] Added {}", long_name));
+            mutated_header_names.push(headers);
+        }
 
-        // 5. Encoding mutations
-        let encoded_versions = vec![
-            "HTTP%2F1.1",       // Slash encoded
-            "HTTP%2F1%2E1",     // Slash and dot encoded
-            "%48%54%54%50/1.1", // Full version encoded
+        // 7. Duplicated names with conflicting values
+        let duplicates = vec![
+            ("Host", "localhost"),                // Conflicting virtual host
+            ("Content-Type", "application/json"), // Conflicting body type
+            ("User-Agent", "curl/7.68.0"),        // Conflicting client
+            ("host", "localhost"),                // Same name differing only in case
         ];
-        for v in encoded_versions {
-            mutated_versions_descriptions.push(format!("[http version] {} -> {}", http_version, v.to_string()));
-            mutated_versions.push(v.to_string());
+
+        for (name, conflicting_value) in duplicates {
+            let original_name = if name == "host" { "Host" } else { name };
+            let original_value = base_headers.get(original_name).unwrap_or("");
+            let mut headers = base_headers.clone();
+            headers.append(name, conflicting_value);
+            mutated_header_names_descriptions.push(format!(
+                "[header name] {}:{} -> {}:{}, {}:{}",
+                original_name, original_value, original_name, original_value, name, conflicting_value
+            ));
+            mutated_header_names.push(headers);
         }
 
-        (mutated_versions_descriptions, mutated_versions)
+        (mutated_header_names_descriptions, mutated_header_names)
     }
 
-    pub fn fuzz_headers(&self, base_url: &str) -> (Vec<String>, Vec<BTreeMap<String, String>>) {
+    pub fn fuzz_body(&self, base_url: &str) -> (Vec<String>, Vec<(Headers, String)>) {
         let normalized_url = http_client::normalize_url(base_url);
         let parsed_url = Url::parse(&normalized_url).expect("Invalid URL format");
         let domain = parsed_url.host_str().unwrap_or("");
 
         let base_headers = http_client::get_default_headers(domain);
 
-        let mut mutated_headers_descriptions = Vec::new();
-        let mut mutated_headers = Vec::new();
+        let mut mutated_bodies_descriptions = Vec::new();
+        let mut mutated_bodies = Vec::new();
+
+        // 1. Empty versus declared length
+        mutated_bodies_descriptions.push(String::from("[body] Empty body with Content-Length:0"));
+        mutated_bodies.push((self.with_body_headers(&base_headers, None, "0"), String::new()));
+
+        // The server has to decide whether to wait for the missing bytes or answer right away
+        mutated_bodies_descriptions.push(String::from("[body] Empty body with Content-Length:16"));
+        mutated_bodies.push((self.with_body_headers(&base_headers, None, "16"), String::new()));
 
-        // 0. Default headers
-        mutated_headers_descriptions.push(format!("[header] Default headers"));
-        mutated_headers.push(base_headers.clone());
-
-        // 1. User-Agent variations
-        let user_agents = vec![
-            "",                                                                               // Empty
-            "curl/7.68.0",                                                                    // Command line tool
-            "Mozilla/4.0 (compatible; MSIE 6.0; Windows NT 5.1)",                             // Old browser
-            "Googlebot/2.1 (+http://www.google.com/bot.html)",                                // Web crawler
-            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:89.0) Gecko/20100101 Firefox/89.0", // Another modern browser
-            "UnknownAgent/1.0",                                                               // Non-standard agent
+        // 2. Well-formed payloads
+        let payloads = vec![
+            ("application/x-www-form-urlencoded", "name=wmap&value=1"),    // Urlencoded form
+            ("application/x-www-form-urlencoded", "name[]=wmap&name[]=1"), // Urlencoded array
+            ("application/json", r#"{"name":"wmap","value":1}"#),          // JSON object
+            ("application/json", r#"["wmap",1]"#),                         // JSON array
         ];
 
-        for user_agent in user_agents {
-            let mut headers = base_headers.clone();
-            headers.insert("User-Agent".to_string(), user_agent.to_string());
-            mutated_headers_descriptions.push(format!(
-                "[header] User-Agent:{} -> User-Agent:{}",
-                base_headers.get("User-Agent").unwrap_or(&"".to_string()),
-                user_agent
-            ));
-            mutated_headers.push(headers);
+        // 3. Malformed payloads
+        let malformed_payloads = vec![
+            ("application/json", r#"{"name":"wmap","value":"#),   // Truncated JSON
+            ("application/json", "{'name': 'wmap'}"),             // Single-quoted JSON
+            ("application/json", "name=wmap&value=1"),            // Urlencoded body declared as JSON
+            ("application/x-www-form-urlencoded", "name=%zz&=1"), // Invalid percent-encoding and empty key
+        ];
+
+        for (content_type, body) in payloads.into_iter().chain(malformed_payloads) {
+            mutated_bodies_descriptions.push(format!("[body] Content-Type:{} -> {}", content_type, body));
+            mutated_bodies.push((self.with_body_headers(&base_headers, Some(content_type), &body.len().to_string()), body.to_string()));
+        }
+
+        // 4. Multipart with bad boundaries
+        let part = "Content-Disposition: form-data; name=\"name\"\r\n\r\nwmap\r\n";
+        let multipart_bodies = vec![
+            ("multipart/form-data; boundary=wmap", format!("--wmap\r\n{}--wmap--\r\n", part), "Valid boundary"),
+            ("multipart/form-data", format!("--wmap\r\n{}--wmap--\r\n", part), "Missing boundary parameter"),
+            ("multipart/form-data; boundary=wmap", format!("--other\r\n{}--other--\r\n", part), "Mismatched boundary"),
+            ("multipart/form-data; boundary=wmap", format!("--wmap\r\n{}", part), "Unterminated body"),
+            ("multipart/form-data; boundary=", format!("--\r\n{}----\r\n", part), "Empty boundary"),
+            ("multipart/form-data; boundary=\"wmap", format!("--wmap\r\n{}--wmap--\r\n", part), "Unbalanced quotes in boundary"),
+        ];
+
+        for (content_type, body, description) in multipart_bodies {
+            mutated_bodies_descriptions.push(format!("[body] Multipart: {}", description));
+            mutated_bodies.push((self.with_body_headers(&base_headers, Some(content_type), &body.len().to_string()), body));
         }
 
-        // 2. Referer manipulations
-        let referer_non_existent_page = format!("{}/non-existent-page", &normalized_url);
-        let referer_https = format!("https://{}", domain);
+        // 5. Oversized payloads, the second one above PHP's default post_max_size of 8M
+        for size in [1024 * 1024, 9 * 1024 * 1024] {
+            let body = format!("name={}", "A".repeat(size));
+            mutated_bodies_descriptions.push(format!("[body] Oversized urlencoded payload of {} bytes", body.len()));
+            mutated_bodies.push((self.with_body_headers(&base_headers, Some("application/x-www-form-urlencoded"), &body.len().to_string()), body));
+        }
 
-        let referers = vec![
-            "",                         // Empty
-            "http://malicious.com",     // Malicious referer
-            &referer_non_existent_page, // Non-existent page
-            &referer_https,             // HTTPS version of the site
+        // 6. Charset mismatch with Content-Type
+        let charset_mismatches = vec![
+            ("application/x-www-form-urlencoded; charset=UTF-16", "name=wmap&value=1"), // UTF-8 body declared as UTF-16
+            ("application/json; charset=ISO-8859-1", r#"{"name":"wmäp✓"}"#),            // Multibyte UTF-8 declared as Latin-1
+            ("text/plain; charset=unknown-charset", "name=wmap"),                       // Unknown charset
         ];
 
-        for referer in referers {
-            let mut headers = base_headers.clone();
-            headers.insert("Referer".to_string(), referer.to_string());
-            mutated_headers_descriptions.push(format!("[header] Referer:{} -> Referer:{}", base_headers.get("Referer").unwrap_or(&"".to_string()), referer));
-            mutated_headers.push(headers);
+        for (content_type, body) in charset_mismatches {
+            mutated_bodies_descriptions.push(format!("[body] Content-Type:{} -> {}", content_type, body));
+            mutated_bodies.push((self.with_body_headers(&base_headers, Some(content_type), &body.len().to_string()), body.to_string()));
         }
 
-        // 3. Content-Type manipulations
-        let content_types = vec![
-            "application/json",                                  // JSON content
-            "multipart/form-data",                               // Form data with files
-            "text/plain",                                        // Plain text
-            "application/xml",                                   // XML content
-            "application/x-www-form-urlencoded; charset=UTF-16", // Different charset
+        (mutated_bodies_descriptions, mutated_bodies)
+    }
+
+    pub fn fuzz_framing(&self, base_url: &str) -> (Vec<String>, Vec<(Headers, String)>) {
+        let normalized_url = http_client::normalize_url(base_url);
+        let parsed_url = Url::parse(&normalized_url).expect("Invalid URL format");
+        let domain = parsed_url.host_str().unwrap_or("");
+
+        let base_headers = http_client::get_default_headers(domain);
+        let body = "name=wmap";
+        let chunked_body = format!("{:x}\r\n{}\r\n0\r\n\r\n", body.len(), body);
+
+        let mut mutated_framings_descriptions = Vec::new();
+        let mut mutated_framings = Vec::new();
+
+        // 1. Content-Length disagreeing with the body
+        let content_lengths = vec![
+            (body.len() + 10).to_string(),         // Longer than the body, the server has to wait or give up
+            (body.len() - 4).to_string(),          // Shorter than the body, the rest looks like a second request
+            "-1".to_string(),                      // Negative
+            "abc".to_string(),                     // Non-numeric
+            "0x9".to_string(),                     // Hexadecimal
+            "+9".to_string(),                      // Explicit sign
+            "9.0".to_string(),                     // Decimal
+            "".to_string(),                        // Empty
+            "99999999999999999999999".to_string(), // Overflowing
         ];
 
-        for content_type in content_types {
+        for content_length in content_lengths {
             let mut headers = base_headers.clone();
-            headers.insert("Content-Type".to_string(), content_type.to_string());
-            mutated_headers_descriptions.push(format!(
-                "[header] Content-Type:{} -> Content-Type:{}",
-                base_headers.get("Content-Type").unwrap_or(&"".to_string()),
-                content_type
-            ));
-            mutated_headers.push(headers);
+            headers.set("Content-Length", &content_length);
+            mutated_framings_descriptions.push(format!("[framing] Content-Length:{} -> Content-Length:{}", body.len(), content_length));
+            mutated_framings.push((headers, body.to_string()));
         }
 
-        // 4. Host manipulations
-        let host_custom_port = format!("{}:8080", domain);
-        let host_subdomain = format!("sub.{}", domain);
+        // 2. Multiple Content-Length headers
+        let multiple_content_lengths = vec![
+            vec![body.len().to_string(), body.len().to_string()], // Repeated, same value
+            vec![body.len().to_string(), "0".to_string()],        // Repeated, conflicting values
+            vec![format!("{}, {}", body.len(), body.len())],      // Comma-separated list in a single header
+        ];
+
+        for content_lengths in multiple_content_lengths {
+            let mut headers = base_headers.clone();
+            for content_length in &content_lengths {
+                headers.append("Content-Length", content_length);
+            }
+            mutated_framings_descriptions.push(format!("[framing] Content-Length:{} -> Content-Length:{}", body.len(), content_lengths.join(", Content-Length:")));
+            mutated_framings.push((headers, body.to_string()));
+        }
 
-        let hosts = vec![
-            "localhost",       // Localhost
-            "127.0.0.1",       // Localhost IP
-            &host_custom_port, // Custom port
-            &host_subdomain,   // Subdomain
+        // 3. Chunked bodies with malformed chunk sizes
+        let malformed_chunked_bodies = vec![
+            (format!("zz\r\n{}\r\n0\r\n\r\n", body), "Non-hex chunk size"),
+            (format!("-9\r\n{}\r\n0\r\n\r\n", body), "Negative chunk size"),
+            (format!("ffffffffffffffffff\r\n{}\r\n0\r\n\r\n", body), "Overflowing chunk size"),
+            (format!("{:x}\r\n{}\r\n0\r\n\r\n", body.len() + 5, body), "Chunk size larger than the data"),
+            (format!("{:x};ext=\"unterminated\r\n{}\r\n0\r\n\r\n", body.len(), body), "Malformed chunk extension"),
+            (format!("{:x}\r\n{}0\r\n\r\n", body.len(), body), "Missing CRLF after chunk data"),
+            (format!("{:x}\r\n{}\r\n", body.len(), body), "Missing last chunk"),
         ];
 
-        for host in hosts {
+        for (chunked, description) in malformed_chunked_bodies {
             let mut headers = base_headers.clone();
-            headers.insert("Host".to_string(), host.to_string());
-            mutated_headers_descriptions.push(format!("[header] Host:{} -> Host:{}", base_headers.get("Host").unwrap_or(&"".to_string()), host));
-            mutated_headers.push(headers);
+            headers.set("Transfer-Encoding", "chunked");
+            mutated_framings_descriptions.push(format!("[framing] Transfer-Encoding:chunked -> {}", description));
+            mutated_framings.push((headers, chunked));
         }
 
-        // 5. X-Forwarded-For manipulations
-        let x_forwarded_for_values = vec![
-            "",                              // Empty
-            "192.168.1.1",                   // Private IP address
-            "10.0.0.1",                      // Another private IP address
-            "203.0.113.195, 198.51.100.101", // Multiple IP addresses
-            "127.0.0.1",                     // Localhost IP
+        // 4. Obfuscated Transfer-Encoding values
+        let transfer_encodings = vec![
+            "chunked ",
+            " chunked",
+            "xchunked",
+            "Chunked",
+            "CHUNKED",
+            "chunked, identity",
+            "identity, chunked",
+            "chunked\t",
+            "\"chunked\"",
         ];
 
-        for x_forwarded_for in x_forwarded_for_values {
+        for transfer_encoding in transfer_encodings {
             let mut headers = base_headers.clone();
-            headers.insert("X-Forwarded-For".to_string(), x_forwarded_for.to_string());
-            mutated_headers_descriptions.push(format!(
-                "[header] X-Forwarded-For:{} -> X-Forwarded-For:{}",
-                base_headers.get("X-Forwarded-For").unwrap_or(&"".to_string()),
-                x_forwarded_for
-            ));
-            mutated_headers.push(headers);
+            headers.set("Transfer-Encoding", transfer_encoding);
+            mutated_framings_descriptions.push(format!("[framing] Transfer-Encoding:chunked -> Transfer-Encoding:{}", transfer_encoding));
+            mutated_framings.push((headers, chunked_body.clone()));
         }
 
-        // 6. Cookie manipulations
-        let cookies = vec![
-            "",                                       // Empty
-            "PHPSESSID=abcdef123456",                 // Valid session ID
-            "PHPSESSID=; path=/; HttpOnly",           // Empty session ID
-            "malicious=1; PHPSESSID=123456789abcdef", // Additional malicious cookie
+        // Obfuscated header lines and repeated Transfer-Encoding headers
+        let raw_transfer_encodings = vec![
+            "Transfer-Encoding : chunked",
+            "Transfer-Encoding:\tchunked",
+            "Transfer_Encoding: chunked",
+            " Transfer-Encoding: chunked",
         ];
 
-        for cookie in cookies {
+        for raw_transfer_encoding in raw_transfer_encodings {
             let mut headers = base_headers.clone();
-            headers.insert("Cookie".to_string(), cookie.to_string());
-            mutated_headers_descriptions.push(format!("[header] Cookie:{} -> Cookie:{}", base_headers.get("Cookie").unwrap_or(&"".to_string()), cookie));
-            mutated_headers.push(headers);
+            headers.append("Transfer-Encoding", "chunked");
+            headers.set_raw("Transfer-Encoding", raw_transfer_encoding);
+            mutated_framings_descriptions.push(format!("[framing] Transfer-Encoding:chunked -> {}", raw_transfer_encoding));
+            mutated_framings.push((headers, chunked_body.clone()));
         }
 
-        // 7. Authorization manipulations
-        let authorizations = vec![
-            "",                           // Empty
-            "Basic dXNlcjpwYXNzd29yZA==", // Basic auth with user:password
-            "Bearer somejwttoken",        // Bearer token
-            "Negotiate YII=",             // Negotiate (Kerberos) token
+        let mut headers = base_headers.clone();
+        headers.append("Transfer-Encoding", "chunked");
+        headers.append("Transfer-Encoding", "identity");
+        mutated_framings_descriptions.push(String::from("[framing] Transfer-Encoding:chunked -> Transfer-Encoding:chunked, Transfer-Encoding:identity"));
+        mutated_framings.push((headers, chunked_body.clone()));
+
+        // 5. Content-Length and Transfer-Encoding together
+        let content_lengths = vec![
+            chunked_body.len().to_string(), // Agrees with the chunked body
+            "0".to_string(),                // Claims there is no body
+            body.len().to_string(),         // Covers only part of the chunked body
         ];
 
-        for authorization in authorizations {
+        for content_length in content_lengths {
             let mut headers = base_headers.clone();
-            headers.insert("Authorization".to_string(), authorization.to_string());
-            mutated_headers_descriptions.push(format!(
-                "[header] Authorization:{} -> Authorization:{}",
-                base_headers.get("Authorization").unwrap_or(&"".to_string()),
-                authorization
-            ));
-            mutated_headers.push(headers);
+            headers.set("Content-Length", &content_length);
+            headers.set("Transfer-Encoding", "chunked");
+            mutated_framings_descriptions.push(format!("[framing] Transfer-Encoding:chunked -> Content-Length:{}, Transfer-Encoding:chunked", content_length));
+            mutated_framings.push((headers, chunked_body.clone()));
         }
 
-        (mutated_headers_descriptions, mutated_headers)
+        // Transfer-Encoding sent first
+        let mut headers = base_headers.clone();
+        headers.set("Transfer-Encoding", "chunked");
+        headers.set("Content-Length", &body.len().to_string());
+        mutated_framings_descriptions.push(format!("[framing] Transfer-Encoding:chunked -> Transfer-Encoding:chunked, Content-Length:{}", body.len()));
+        mutated_framings.push((headers, chunked_body.clone()));
+
+        (mutated_framings_descriptions, mutated_framings)
+    }
+
+    pub fn fuzz_raw_bytes(&self, method: &str, request_target: &str, base_url: &str) -> (Vec<String>, Vec<RawRequestParts>) {
+        let normalized_url = http_client::normalize_url(base_url);
+        let parsed_url = Url::parse(&normalized_url).expect("Invalid URL format");
+        let domain = parsed_url.host_str().unwrap_or("");
+
+        let base_headers = http_client::get_default_headers(domain);
+
+        let mut mutated_raw_bytes_descriptions = Vec::new();
+        let mut mutated_raw_bytes = Vec::new();
+
+        let payloads: Vec<&[u8]> = vec![
+            b"\x80",                 // Lone continuation byte
+            b"\xff",                 // Byte never valid in UTF-8
+            b"\xc0\xaf",             // Overlong '/'
+            b"\xe0\x80\xaf",         // Three-byte overlong '/'
+            b"\xc0\x80",             // Overlong NUL
+            b"\x00",                 // Raw NUL
+            b"\xc3\x28",             // Truncated two-byte sequence
+            b"\xed\xa0\x80",         // UTF-16 surrogate encoded as UTF-8
+            b"\xf8\x88\x80\x80\x80", // Obsolete five-byte sequence
+            b"\xef\xbb\xbf",         // UTF-8 byte order mark
+        ];
+
+        for payload in payloads {
+            // 1. Method
+            let mutated_method = [method.as_bytes(), payload].concat();
+            mutated_raw_bytes_descriptions.push(format!("[raw bytes] {} -> {}", method, mutated_method.escape_ascii()));
+            mutated_raw_bytes.push((mutated_method, request_target.as_bytes().to_vec(), base_headers.clone()));
+
+            // 2. Request target
+            let mutated_request_target = [request_target.as_bytes(), payload].concat();
+            mutated_raw_bytes_descriptions.push(format!("[raw bytes] {} -> {}", request_target, mutated_request_target.escape_ascii()));
+            mutated_raw_bytes.push((method.as_bytes().to_vec(), mutated_request_target, base_headers.clone()));
+
+            // 3. Header values
+            for name in ["Host", "User-Agent"] {
+                let value = base_headers.get(name).unwrap_or("");
+                let mutated_value = [value.as_bytes(), payload].concat();
+                let mut headers = base_headers.clone();
+                headers.set_bytes(name, &mutated_value);
+                mutated_raw_bytes_descriptions.push(format!("[raw bytes] {}:{} -> {}:{}", name, value, name, mutated_value.escape_ascii()));
+                mutated_raw_bytes.push((method.as_bytes().to_vec(), request_target.as_bytes().to_vec(), headers));
+            }
+        }
+
+        (mutated_raw_bytes_descriptions, mutated_raw_bytes)
+    }
+
+    fn apply_definitions(&self, field: Field, original: &str) -> Vec<String> {
+        self.definitions
+            .iter()
+            .filter(|definition| definition.field == field)
+            .flat_map(|definition| definition.apply(original, &[]))
+            .collect()
+    }
+
+    fn with_body_headers(&self, base_headers: &Headers, content_type: Option<&str>, content_length: &str) -> Headers {
+        let mut headers = base_headers.clone();
+        if let Some(content_type) = content_type {
+            headers.set("Content-Type", content_type);
+        }
+        headers.set("Content-Length", content_length);
+        headers
     }
 
     fn suppress_char(&self, input: &str, i: usize) -> Option<String> {
@@ -441,19 +537,6 @@ impl Fuzzer {
         results
     }
 
-    fn replace_chars(&self, input: &str, replacements: &[(char, &str)]) -> Vec<String> {
-        let mut results = Vec::new();
-
-        for (original, replacement) in replacements {
-            let mutated = input.replace(*original, replacement);
-            if mutated != input {
-                results.push(mutated);
-            }
-        }
-
-        results
-    }
-
     fn alternate_case(&self, input: &str) -> String {
         input
             .chars()
@@ -462,21 +545,6 @@ impl Fuzzer {
             .collect()
     }
 
-    fn add_whitespaces_between_chars(&self, input: &str) -> String {
-        input.chars().collect::<String>().replace("", " ").trim().to_string()
-    }
-
-    fn concatenate_with_methods(&self, input: &str, methods_to_append: &[&str]) -> Vec<String> {
-        let mut results = Vec::new();
-
-        for method in methods_to_append {
-            let concatenated = format!("{}{}", input, method);
-            results.push(concatenated);
-        }
-
-        results
-    }
-
     fn extract_path_and_resource(&self, request_target: &str) -> (String, Option<String>) {
         let parts: Vec<&str> = request_target.split('/').collect();
 
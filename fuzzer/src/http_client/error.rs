@@ -0,0 +1,92 @@
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Connect,
+    Write,
+    Read,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Connect => "connect",
+            Phase::Write => "write",
+            Phase::Read => "read",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum TransportError {
+    ConnectRefused,
+    Timeout(Phase),
+    ResetByPeer,
+    Tls(String),
+    TruncatedResponse,
+    EmptyResponse,
+    Io(String),
+}
+
+impl TransportError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TransportError::ConnectRefused => "connect_refused",
+            TransportError::Timeout(_) => "timeout",
+            TransportError::ResetByPeer => "reset_by_peer",
+            TransportError::Tls(_) => "tls",
+            TransportError::TruncatedResponse => "truncated_response",
+            TransportError::EmptyResponse => "empty_response",
+            TransportError::Io(_) => "io",
+        }
+    }
+
+    // Socket timeouts carry no context, so the caller says which phase of the exchange was running
+    pub fn from_io(e: io::Error, phase: Phase) -> Self {
+        // rustls reports handshake and record failures wrapped in an io::Error
+        if let Some(tls_error) = e.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+            return TransportError::Tls(tls_error.to_string());
+        }
+
+        match e.kind() {
+            io::ErrorKind::ConnectionRefused => TransportError::ConnectRefused,
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => TransportError::Timeout(phase),
+            io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe => TransportError::ResetByPeer,
+            io::ErrorKind::UnexpectedEof => TransportError::TruncatedResponse,
+            _ => TransportError::Io(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Timeout(phase) => write!(f, "{}: {}", self.kind(), phase.name()),
+            TransportError::Tls(detail) | TransportError::Io(detail) => write!(f, "{}: {}", self.kind(), detail),
+            _ => write!(f, "{}", self.kind()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    Error(TransportError),
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Error(TransportError::Timeout(Phase::Connect)) => "connect_timeout",
+            Outcome::Error(TransportError::Timeout(Phase::Write)) => "write_timeout",
+            Outcome::Error(TransportError::Timeout(Phase::Read)) => "read_timeout",
+            Outcome::Error(e) => e.kind(),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+#[derive(Debug, Clone)]
+enum HeaderLine {
+    Field(String, String),
+    // Written verbatim, for lines that are not a well-formed `name: value` pair or are not valid UTF-8
+    Raw(Vec<u8>),
+}
+
+// Request headers in wire order. Names keep their exact casing and may repeat
+#[derive(Debug, Clone, Default)]
+pub struct Headers {
+    entries: Vec<HeaderLine>,
+}
+
+impl Headers {
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries.push(HeaderLine::Field(name.to_string(), value.to_string()));
+    }
+
+    // Replaces the value of the first header with exactly this name, keeping its position, or appends it
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.field_mut(name) {
+            Some(HeaderLine::Field(_, existing)) => *existing = value.to_string(),
+            _ => self.append(name, value),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter().find_map(|entry| match entry {
+            HeaderLine::Field(key, value) if key == name => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    // Renames the first header with exactly this name, keeping its value and position
+    pub fn rename(&mut self, name: &str, new_name: &str) {
+        if let Some(HeaderLine::Field(key, _)) = self.field_mut(name) {
+            *key = new_name.to_string();
+        }
+    }
+
+    // Replaces the first header with exactly this name by a line sent as is
+    pub fn set_raw(&mut self, name: &str, line: &str) {
+        if let Some(entry) = self.field_mut(name) {
+            *entry = HeaderLine::Raw(line.as_bytes().to_vec());
+        }
+    }
+
+    // Replaces the value of the first header with exactly this name by arbitrary bytes
+    pub fn set_bytes(&mut self, name: &str, value: &[u8]) {
+        if let Some(entry) = self.field_mut(name) {
+            *entry = HeaderLine::Raw([format!("{}: ", name).as_bytes(), value].concat());
+        }
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.entries.iter().map(|entry| match entry {
+            HeaderLine::Field(name, value) => format!("{}: {}", name, value).into_bytes(),
+            HeaderLine::Raw(line) => line.clone(),
+        })
+    }
+
+    pub fn reversed(&self) -> Self {
+        Self {
+            entries: self.entries.iter().rev().cloned().collect(),
+        }
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut HeaderLine> {
+        self.entries.iter_mut().find(|entry| matches!(entry, HeaderLine::Field(key, _) if key == name))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().map(|(name, value)| HeaderLine::Field(name.into(), value.into())).collect(),
+        }
+    }
+}
@@ -1,14 +1,67 @@
+pub mod error;
+pub mod headers;
+pub mod response;
+pub mod response_reader;
 pub mod spacing_type;
-
+pub mod stream;
+pub mod timeouts;
+pub mod tls;
+
+use error::{Outcome, Phase, TransportError};
+use headers::Headers;
+use lazy_static::lazy_static;
+use response::{HttpResponse, ParsedResponse, Timing};
 use spacing_type::SpacingType;
-use std::collections::BTreeMap;
-use std::io::{Read, Write};
-use std::net::{Shutdown, TcpStream};
-use std::time::Instant;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use stream::Stream;
+use timeouts::Timeouts;
+use tls::{TlsConnector, TlsOptions};
 use url::Url;
 
-pub fn craft_request(method: &str, request_target: &str, http_version: &str, headers: &BTreeMap<String, String>, spacing_type: Option<&SpacingType>) -> String {
-    let mut request_line = format!("{} {} {}\r\n", method, request_target, http_version);
+pub struct HttpClient {
+    tls_connector: TlsConnector,
+    timeouts: Timeouts,
+}
+
+impl HttpClient {
+    pub fn init(tls_options: TlsOptions, timeouts: Timeouts) -> Result<(), String> {
+        let tls_connector = TlsConnector::new(&tls_options)?;
+        let mut client = GLOBAL_CLIENT.write().unwrap();
+        client.tls_connector = tls_connector;
+        client.timeouts = timeouts;
+        Ok(())
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient {
+            tls_connector: TlsConnector::new(&TlsOptions::default()).expect("Built-in root certificates load"),
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref GLOBAL_CLIENT: RwLock<HttpClient> = RwLock::new(HttpClient::default());
+}
+
+pub fn initialize_client(matches: &clap::ArgMatches) -> Result<(), String> {
+    HttpClient::init(TlsOptions::from_args(matches), Timeouts::from_args(matches))
+}
+
+pub fn craft_request(
+    method: impl AsRef<[u8]>,
+    request_target: impl AsRef<[u8]>,
+    http_version: impl AsRef<[u8]>,
+    headers: &Headers,
+    spacing_type: Option<&SpacingType>,
+    body: Option<&[u8]>,
+) -> Vec<u8> {
+    let mut request_line = [method.as_ref(), b" ", request_target.as_ref(), b" ", http_version.as_ref(), b"\r\n"].concat();
 
     // Apply spacing mutation to the request line
     if let Some(mutation_type) = spacing_type {
@@ -17,80 +70,142 @@ pub fn craft_request(method: &str, request_target: &str, http_version: &str, hea
 
     // Add headers
     let mut request = request_line;
-    for (key, value) in headers {
-        request.push_str(&format!("{}: {}\r\n", key, value));
+    for line in headers.lines() {
+        request.extend_from_slice(&line);
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(b"\r\n");
+
+    // The body is written as is; framing headers are up to the caller so they can be mutated too
+    if let Some(body) = body {
+        request.extend_from_slice(body);
     }
-    request.push_str("\r\n");
 
     request
 }
 
-pub fn send_request(target_url: &str, request: &str) -> (String, u128) {
-    let (host, port, _path) = parse_url(target_url);
-
-    // Open a TCP stream to the server
-    let mut stream = TcpStream::connect(format!("{}:{}", host, port)).expect("Failed to connect to server");
+pub fn send_request(target_url: &str, request: &[u8]) -> HttpResponse {
+    let connect_start = Instant::now();
+    let mut tcp_connected = None;
+    let mut stream = match connect(target_url, &mut tcp_connected) {
+        Ok(stream) => stream,
+        Err(e) => {
+            let connect_time = micros(connect_start.elapsed());
+            return HttpResponse {
+                raw: Vec::new(),
+                parsed: None,
+                response_time: 0,
+                timing: Timing {
+                    connect: connect_time,
+                    tcp_connect: tcp_connected.map(|tcp_connected: Instant| micros(tcp_connected - connect_start)),
+                    first_byte: None,
+                    total: connect_time,
+                },
+                outcome: Outcome::Error(e),
+            };
+        }
+    };
+    let connect_time = connect_start.elapsed();
 
     let start_time = Instant::now();
 
-    // Send the crafted request
-    stream.write_all(request.as_bytes()).expect("Failed to write to stream");
-
-    // Read the response
+    // Keep whatever was received even if the exchange fails midway
     let mut response = Vec::new();
-    let mut buffer = [0; 4096];
+    let mut first_byte = None;
+    let result = exchange(&mut stream, request, &mut response, &mut first_byte);
 
-    loop {
-        let bytes_read = stream.read(&mut buffer).expect("Error reading response");
+    let duration = start_time.elapsed();
 
-        if bytes_read == 0 {
-            break;
-        }
+    // The server may already have closed the connection, which is not a failure of the exchange
+    let _ = stream.shutdown();
 
-        response.extend_from_slice(&buffer[..bytes_read]);
+    let outcome = match result {
+        Ok(()) => Outcome::Ok,
+        Err(e) => Outcome::Error(e),
+    };
 
-        if response.windows(4).any(|window| window == b"\r\n\r\n") {
-            break;
-        }
+    HttpResponse {
+        parsed: ParsedResponse::parse(&response),
+        raw: response,
+        response_time: duration.as_millis(),
+        timing: Timing {
+            connect: micros(connect_time),
+            tcp_connect: tcp_connected.map(|tcp_connected: Instant| micros(tcp_connected - connect_start)),
+            first_byte: first_byte.map(|first_byte: Instant| micros(first_byte - start_time)),
+            total: micros(connect_time + duration),
+        },
+        outcome,
     }
+}
 
-    let response_str = String::from_utf8_lossy(&response);
-
-    // Split headers and body
-    let headers_end = response_str.find("\r\n\r\n").map(|index| index + 4).unwrap_or(response_str.len());
-
-    let headers_str = &response_str[..headers_end];
-    let body_str = &response_str[headers_end..];
+// Notes when the TCP connection is up, before any TLS handshake
+fn connect(target_url: &str, tcp_connected: &mut Option<Instant>) -> Result<Stream, TransportError> {
+    let (host, port, _path) = parse_url(target_url);
+    let client = GLOBAL_CLIENT.read().unwrap();
 
-    // Check Content-Length if available
-    let content_length = headers_str
-        .lines()
-        .find(|&line| line.starts_with("Content-Length:"))
-        .and_then(|line| line.split(": ").nth(1))
-        .and_then(|len| len.trim().parse::<usize>().ok())
-        .unwrap_or(0);
+    // Open a TCP stream to the server, wrapped in a TLS session for https:// targets
+    let tcp_stream = connect_tcp(&host, port, &client.timeouts)?;
+    *tcp_connected = Some(Instant::now());
+    let stream = if is_https(target_url) {
+        Stream::Tls(Box::new(client.tls_connector.connect(&host, tcp_stream, &client.timeouts)?))
+    } else {
+        Stream::Plain(tcp_stream)
+    };
 
-    // If content length not 0, read the rest of the body
-    let mut body = body_str.to_string();
-    if content_length > body.len() {
-        let mut buffer = vec![0; content_length - body.len()];
-        let mut total_bytes_read = body.len();
+    Ok(stream)
+}
 
-        while total_bytes_read < content_length {
-            let bytes_read = stream.read(&mut buffer).expect("Error reading response body");
-            if bytes_read == 0 {
-                break;
+fn connect_tcp(host: &str, port: u16, timeouts: &Timeouts) -> Result<TcpStream, TransportError> {
+    let addrs = (host, port).to_socket_addrs().map_err(|e| TransportError::Io(e.to_string()))?;
+
+    // Try every resolved address, reporting the last failure if none accepts the connection
+    let mut last_error = TransportError::Io(format!("{} did not resolve to any address", host));
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeouts.connect) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(timeouts.write)).map_err(|e| TransportError::Io(e.to_string()))?;
+                stream.set_read_timeout(Some(timeouts.read)).map_err(|e| TransportError::Io(e.to_string()))?;
+                return Ok(stream);
             }
-            total_bytes_read += bytes_read;
-            body.push_str(&String::from_utf8_lossy(&buffer[..bytes_read]));
+            Err(e) => last_error = TransportError::from_io(e, Phase::Connect),
         }
     }
 
-    let duration = start_time.elapsed().as_millis();
+    Err(last_error)
+}
+
+fn exchange(stream: &mut Stream, request: &[u8], response: &mut Vec<u8>, first_byte: &mut Option<Instant>) -> Result<(), TransportError> {
+    // Responses to HEAD carry framing headers but never a body
+    let head_request = request.trim_ascii_start().split(|byte| *byte == b' ' || *byte == b'\t').next() == Some(b"HEAD");
+
+    // Send the crafted request
+    if let Err(e) = stream.write_all(request) {
+        // Servers may answer and close before reading the whole request, so keep any early response
+        let _ = response_reader::read_response(&mut FirstByteReader { inner: stream, first_byte }, response, head_request);
+        return Err(TransportError::from_io(e, Phase::Write));
+    }
+
+    response_reader::read_response(&mut FirstByteReader { inner: stream, first_byte }, response, head_request)
+}
+
+// Notes when the first byte of the response arrives
+struct FirstByteReader<'a, R> {
+    inner: &'a mut R,
+    first_byte: &'a mut Option<Instant>,
+}
 
-    stream.shutdown(Shutdown::Both).expect("Shutdown failed");
+impl<R: Read> Read for FirstByteReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 && self.first_byte.is_none() {
+            *self.first_byte = Some(Instant::now());
+        }
+        Ok(read)
+    }
+}
 
-    (response_str.to_string(), duration)
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
 }
 
 pub fn parse_url(url: &str) -> (String, u16, String) {
@@ -118,6 +233,10 @@ pub fn parse_url(url: &str) -> (String, u16, String) {
     (host, port, path)
 }
 
+pub fn is_https(url: &str) -> bool {
+    url.starts_with("https://")
+}
+
 pub fn normalize_url(base_url: &str) -> String {
     let normalized_url = if base_url.starts_with("http://") || base_url.starts_with("https://") {
         base_url.to_string()
@@ -128,7 +247,7 @@ pub fn normalize_url(base_url: &str) -> String {
     normalized_url
 }
 
-pub fn get_default_headers(domain: &str) -> BTreeMap<String, String> {
+pub fn get_default_headers(domain: &str) -> Headers {
     let normalized_url = normalize_url(domain);
     let parsed_url = Url::parse(&normalized_url).expect("Invalid URL format");
     let domain = parsed_url.host_str().unwrap_or("");
@@ -144,6 +263,73 @@ pub fn get_default_headers(domain: &str) -> BTreeMap<String, String> {
         ("Cookie", "PHPSESSID=123456789abcdef"),
     ]
     .into_iter()
-    .map(|(k, v)| (k.to_string(), v.to_string()))
     .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::crypto::ring;
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+    use rustls::{ServerConfig, ServerConnection, StreamOwned};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    const HANDSHAKE_DELAY: Duration = Duration::from_millis(200);
+
+    // Answers one https request, holding back its side of the handshake for HANDSHAKE_DELAY
+    fn serve_https_once() -> u16 {
+        let certified = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key)
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            let (tcp_stream, _) = listener.accept().unwrap();
+            thread::sleep(HANDSHAKE_DELAY);
+
+            let mut stream = StreamOwned::new(ServerConnection::new(Arc::new(config)).unwrap(), tcp_stream);
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+            stream.conn.send_close_notify();
+            stream.flush().unwrap();
+        });
+
+        port
+    }
+
+    #[test]
+    fn https_handshake_counts_as_connecting() {
+        HttpClient::init(
+            TlsOptions {
+                insecure: true,
+                ..TlsOptions::default()
+            },
+            Timeouts::default(),
+        )
+        .unwrap();
+        let port = serve_https_once();
+
+        let response = send_request(&format!("https://127.0.0.1:{}/", port), b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+        assert!(matches!(response.outcome, Outcome::Ok));
+        assert_eq!(response.parsed.and_then(|parsed| parsed.head.status_code), Some(200));
+        assert!(response.timing.connect >= micros(HANDSHAKE_DELAY));
+        // The network baseline of a target comes from the TCP connection alone
+        assert!(response.timing.tcp_connect.unwrap() < micros(HANDSHAKE_DELAY));
+        // Before the handshake was driven while connecting, it stalled the request write instead
+        assert!(response.timing.first_byte.unwrap() < micros(HANDSHAKE_DELAY));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::http_client::error::Outcome;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub struct HttpResponse {
+    // Exactly as received, bodies may be compressed or binary
+    pub raw: Vec<u8>,
+    pub parsed: Option<ParsedResponse>,
+    pub response_time: u128,
+    pub timing: Timing,
+    pub outcome: Outcome,
+}
+
+// One exchange in microseconds: the TCP connection with the whole TLS handshake of https targets, the first response
+// byte counted from the start of the request write, and the total of both
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    pub connect: u64,
+    // The TCP connection alone, None when it failed
+    pub tcp_connect: Option<u64>,
+    pub first_byte: Option<u64>,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseHead {
+    pub protocol_version: String,
+    pub status_code: Option<u16>,
+    pub reason_phrase: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl ResponseHead {
+    // Parses a status line and header section, keeping header order and the original name casing
+    pub fn parse(head: &str) -> Self {
+        let mut lines = head.lines();
+        let status_line = lines.next().unwrap_or("");
+
+        // The reason phrase is optional and may contain spaces
+        let mut status_parts = status_line.splitn(3, ' ');
+        let protocol_version = status_parts.next().unwrap_or("").to_string();
+        let status_code = status_parts.next().and_then(|code| code.trim().parse::<u16>().ok());
+        let reason_phrase = status_parts.next().unwrap_or("").trim().to_string();
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines {
+            if line.is_empty() {
+                break;
+            }
+
+            // Obsolete line folding continues the previous header value
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+
+            match line.split_once(':') {
+                Some((name, value)) => headers.push((name.to_string(), value.trim().to_string())),
+                None => headers.push((line.to_string(), String::new())),
+            }
+        }
+
+        Self {
+            protocol_version,
+            status_code,
+            reason_phrase,
+            headers,
+        }
+    }
+
+    pub fn header_values(&self, name: &str) -> Vec<&str> {
+        self.headers.iter().filter(|(key, _)| key.trim().eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str()).collect()
+    }
+
+    pub fn is_interim(&self) -> bool {
+        matches!(self.status_code, Some(100..=199)) && self.status_code != Some(101)
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        let transfer_encodings = self.header_values("Transfer-Encoding").join(",");
+        transfer_encodings.rsplit(',').next().is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedResponse {
+    #[serde(flatten)]
+    pub head: ResponseHead,
+    pub body_length: usize,
+    pub body_sha256: String,
+}
+
+impl ParsedResponse {
+    // Parses the final response in `raw`, skipping any interim 1xx responses. Returns None if there is no status line
+    pub fn parse(raw: &[u8]) -> Option<Self> {
+        let (head, body) = final_response(raw)?;
+
+        Some(Self {
+            head,
+            body_length: body.len(),
+            body_sha256: Sha256::digest(&body).iter().map(|byte| format!("{:02x}", byte)).collect(),
+        })
+    }
+
+    // Payload of the final response in `raw`, without the chunked transfer coding
+    pub fn body(raw: &[u8]) -> Option<Vec<u8>> {
+        final_response(raw).map(|(_, body)| body)
+    }
+}
+
+fn final_response(raw: &[u8]) -> Option<(ResponseHead, Vec<u8>)> {
+    let mut pos = 0;
+
+    loop {
+        if !raw[pos..].starts_with(b"HTTP/") {
+            return None;
+        }
+
+        let headers_end = find(&raw[pos..], b"\r\n\r\n").map(|index| pos + index + 4).unwrap_or(raw.len());
+        let head = ResponseHead::parse(&String::from_utf8_lossy(&raw[pos..headers_end]));
+
+        if head.is_interim() && headers_end < raw.len() {
+            pos = headers_end;
+            continue;
+        }
+
+        // The payload as the application produced it, without the chunked transfer coding
+        let raw_body = &raw[headers_end..];
+        let body = if head.is_chunked() {
+            decode_chunked(raw_body).unwrap_or_else(|| raw_body.to_vec())
+        } else {
+            raw_body.to_vec()
+        };
+
+        return Some((head, body));
+    }
+}
+
+fn decode_chunked(mut raw: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+
+    loop {
+        let line_end = find(raw, b"\r\n")?;
+        let size_line = String::from_utf8_lossy(&raw[..line_end]).to_string();
+        let chunk_size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16).ok()?;
+
+        if chunk_size == 0 {
+            return Some(body);
+        }
+
+        let chunk_start = line_end + 2;
+        let chunk_end = chunk_start.checked_add(chunk_size)?;
+        body.extend_from_slice(raw.get(chunk_start..chunk_end)?);
+        raw = raw.get(chunk_end + 2..)?;
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_size_past_the_address_space_keeps_the_raw_body() {
+        let raw_body = b"ffffffffffffffff\r\nabc\r\n0\r\n\r\n";
+        let (_, body) = final_response(&[b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".as_slice(), raw_body].concat()).unwrap();
+
+        assert_eq!(body, raw_body);
+    }
+}
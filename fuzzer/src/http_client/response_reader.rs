@@ -0,0 +1,258 @@
+use crate::http_client::error::{Phase, TransportError};
+use crate::http_client::response::ResponseHead;
+use std::io::Read;
+
+enum BodyFraming {
+    Empty,
+    ContentLength(usize),
+    Chunked,
+    CloseDelimited,
+}
+
+// Reads one complete HTTP/1.x response (including any interim 1xx responses) into `response`
+pub fn read_response<R: Read>(stream: &mut R, response: &mut Vec<u8>, head_request: bool) -> Result<(), TransportError> {
+    let mut pos = 0;
+
+    loop {
+        let headers_end = match read_until(stream, response, pos, b"\r\n\r\n")? {
+            Some(end) => end,
+            None if response.is_empty() => return Err(TransportError::EmptyResponse),
+            // HTTP/0.9 style responses carry no status line and end when the connection closes
+            None if pos == 0 && !response.starts_with(b"HTTP/") => return Ok(()),
+            None => return Err(TransportError::TruncatedResponse),
+        };
+
+        let head = ResponseHead::parse(&String::from_utf8_lossy(&response[pos..headers_end]));
+
+        // Interim responses are followed by the final one on the same connection
+        if head.is_interim() {
+            pos = headers_end;
+            continue;
+        }
+
+        return match body_framing(&head, head_request) {
+            BodyFraming::Empty => Ok(()),
+            // A length past the address space cannot be honoured, so the body ends when the connection closes
+            BodyFraming::ContentLength(length) => match headers_end.checked_add(length) {
+                Some(total_length) => read_length(stream, response, total_length),
+                None => read_to_close(stream, response),
+            },
+            BodyFraming::Chunked => read_chunked(stream, response, headers_end),
+            BodyFraming::CloseDelimited => read_to_close(stream, response),
+        };
+    }
+}
+
+fn body_framing(head: &ResponseHead, head_request: bool) -> BodyFraming {
+    if head_request || matches!(head.status_code, Some(100..=199) | Some(204) | Some(304)) {
+        return BodyFraming::Empty;
+    }
+
+    // Transfer-Encoding takes precedence over Content-Length, and only a final chunked coding delimits the body
+    if !head.header_values("Transfer-Encoding").is_empty() {
+        return if head.is_chunked() { BodyFraming::Chunked } else { BodyFraming::CloseDelimited };
+    }
+
+    match head.header_values("Content-Length").first().map(|len| len.parse::<usize>()) {
+        Some(Ok(length)) => BodyFraming::ContentLength(length),
+        _ => BodyFraming::CloseDelimited,
+    }
+}
+
+fn read_chunked<R: Read>(stream: &mut R, response: &mut Vec<u8>, mut pos: usize) -> Result<(), TransportError> {
+    loop {
+        let line_end = read_until(stream, response, pos, b"\r\n")?.ok_or(TransportError::TruncatedResponse)?;
+        let size_line = String::from_utf8_lossy(&response[pos..line_end - 2]).to_string();
+
+        // Chunk extensions follow a ';' and are ignored
+        let size_str = size_line.split(';').next().unwrap_or("").trim();
+        let chunk_size = match usize::from_str_radix(size_str, 16) {
+            Ok(size) => size,
+            // A malformed chunk size leaves no way to find the end of the body
+            Err(_) => return read_to_close(stream, response),
+        };
+
+        // Chunk data is followed by its own CRLF
+        let Some(chunk_end) = line_end.checked_add(chunk_size).and_then(|end| end.checked_add(2)) else {
+            return read_to_close(stream, response);
+        };
+
+        if chunk_size == 0 {
+            // Skip trailer fields up to the empty line that ends the message
+            let mut line_start = line_end;
+            loop {
+                let next_line = read_until(stream, response, line_start, b"\r\n")?.ok_or(TransportError::TruncatedResponse)?;
+                if next_line == line_start + 2 {
+                    return Ok(());
+                }
+                line_start = next_line;
+            }
+        }
+
+        pos = chunk_end;
+        read_length(stream, response, pos)?;
+    }
+}
+
+fn read_length<R: Read>(stream: &mut R, response: &mut Vec<u8>, total_length: usize) -> Result<(), TransportError> {
+    while response.len() < total_length {
+        if fill(stream, response)? == 0 {
+            return Err(TransportError::TruncatedResponse);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_to_close<R: Read>(stream: &mut R, response: &mut Vec<u8>) -> Result<(), TransportError> {
+    while fill(stream, response)? != 0 {}
+
+    Ok(())
+}
+
+// Returns the index just past the first occurrence of `pattern` at or after `from`, or None if the connection closes first
+fn read_until<R: Read>(stream: &mut R, response: &mut Vec<u8>, from: usize, pattern: &[u8]) -> Result<Option<usize>, TransportError> {
+    let mut search_from = from;
+
+    loop {
+        if let Some(index) = response[search_from..].windows(pattern.len()).position(|window| window == pattern) {
+            return Ok(Some(search_from + index + pattern.len()));
+        }
+
+        // Patterns may straddle two reads
+        search_from = response.len().saturating_sub(pattern.len() - 1).max(from);
+
+        if fill(stream, response)? == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+fn fill<R: Read>(stream: &mut R, response: &mut Vec<u8>) -> Result<usize, TransportError> {
+    let mut buffer = [0; 4096];
+    let bytes_read = stream.read(&mut buffer).map_err(|e| TransportError::from_io(e, Phase::Read))?;
+    response.extend_from_slice(&buffer[..bytes_read]);
+
+    Ok(bytes_read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out one byte per read, so the reader has to stop exactly where the message ends
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buffer[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    fn read(raw: &[u8], head_request: bool) -> (Result<(), TransportError>, Vec<u8>) {
+        let mut response = Vec::new();
+        let result = read_response(&mut ByteByByte(raw), &mut response, head_request);
+        (result, response)
+    }
+
+    #[test]
+    fn chunked_body_ends_after_the_trailers() {
+        let message = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4;name=value\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: never\r\nX-Checksum: 1\r\n\r\n";
+        let (result, response) = read(&[message.as_slice(), b"HTTP/1.1 200 OK\r\n"].concat(), false);
+
+        assert!(result.is_ok());
+        assert_eq!(response, message);
+    }
+
+    #[test]
+    fn chunked_body_without_trailers() {
+        let message = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+        let (result, response) = read(&[message.as_slice(), b"left over"].concat(), false);
+
+        assert!(result.is_ok());
+        assert_eq!(response, message);
+    }
+
+    #[test]
+    fn chunked_body_cut_before_the_last_chunk_is_truncated() {
+        let (result, _) = read(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n", false);
+
+        assert!(matches!(result, Err(TransportError::TruncatedResponse)));
+    }
+
+    #[test]
+    fn chunk_size_past_the_address_space_reads_until_close() {
+        let message = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\nabc";
+        let (result, response) = read(message, false);
+
+        assert!(result.is_ok());
+        assert_eq!(response, message);
+    }
+
+    #[test]
+    fn content_length_past_the_address_space_reads_until_close() {
+        let message = b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\nabc";
+        let (result, response) = read(message, false);
+
+        assert!(result.is_ok());
+        assert_eq!(response, message);
+    }
+
+    #[test]
+    fn head_response_has_no_body() {
+        for head in [
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n".as_slice(),
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".as_slice(),
+            b"HTTP/1.1 200 OK\r\n\r\n".as_slice(),
+        ] {
+            let (result, response) = read(&[head, b"hello"].concat(), true);
+
+            assert!(result.is_ok());
+            assert_eq!(response, head);
+        }
+    }
+
+    #[test]
+    fn no_content_and_not_modified_responses_have_no_body() {
+        for head in [
+            b"HTTP/1.1 204 No Content\r\nContent-Length: 5\r\n\r\n".as_slice(),
+            b"HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n".as_slice(),
+            b"HTTP/1.1 304 Not Modified\r\nTransfer-Encoding: chunked\r\n\r\n".as_slice(),
+        ] {
+            let (result, response) = read(&[head, b"hello"].concat(), false);
+
+            assert!(result.is_ok());
+            assert_eq!(response, head);
+        }
+    }
+
+    #[test]
+    fn interim_responses_are_followed_by_the_final_one() {
+        let message = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let (result, response) = read(&[message.as_slice(), b"left over"].concat(), false);
+
+        assert!(result.is_ok());
+        assert_eq!(response, message);
+    }
+
+    #[test]
+    fn switching_protocols_ends_the_response() {
+        let head = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+        let (result, response) = read(&[head.as_slice(), b"\x81\x05hello"].concat(), false);
+
+        assert!(result.is_ok());
+        assert_eq!(response, head);
+    }
+
+    #[test]
+    fn interim_response_without_a_final_one_is_truncated() {
+        let (result, _) = read(b"HTTP/1.1 100 Continue\r\n\r\n", false);
+
+        assert!(matches!(result, Err(TransportError::TruncatedResponse)));
+    }
+}
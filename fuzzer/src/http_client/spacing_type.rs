@@ -11,36 +11,50 @@ pub enum SpacingType {
 }
 
 impl SpacingType {
-    pub fn apply(&self, input: &str) -> String {
+    pub fn apply(&self, input: &[u8]) -> Vec<u8> {
         match self {
-            SpacingType::AllTabs => input.replace(" ", "\t"),
-            SpacingType::AllSpaces => input.replace("\t", " "),
-            SpacingType::DoubleSpaces => input.replace(" ", "  "),
-            SpacingType::MultipleSpaces => input.replace(" ", "    "),
-            SpacingType::NullTerminated => input.replace("\r\n", "\0\r\n"),
-            SpacingType::MultipleLineBreaks => input.replace("\r\n", "\r\n\r\n"),
+            SpacingType::AllTabs => replace_bytes(input, b" ", b"\t"),
+            SpacingType::AllSpaces => replace_bytes(input, b"\t", b" "),
+            SpacingType::DoubleSpaces => replace_bytes(input, b" ", b"  "),
+            SpacingType::MultipleSpaces => replace_bytes(input, b" ", b"    "),
+            SpacingType::NullTerminated => replace_bytes(input, b"\r\n", b"\0\r\n"),
+            SpacingType::MultipleLineBreaks => replace_bytes(input, b"\r\n", b"\r\n\r\n"),
             SpacingType::LeadingTrailingTabs => {
-                let mut result = String::new();
-                result.push_str("\t");
-                result.push_str(input);
-                result.push_str("\t");
+                let mut result = Vec::new();
+                result.push(b'\t');
+                result.extend_from_slice(input);
+                result.push(b'\t');
                 result
             }
             SpacingType::LeadingTrailingWhitespaces => {
-                let mut result = String::new();
-                result.push_str(" ");
-                result.push_str(input);
-                result.push_str(" ");
+                let mut result = Vec::new();
+                result.push(b' ');
+                result.extend_from_slice(input);
+                result.push(b' ');
                 result
             }
             SpacingType::ControlChars => input
-                .chars()
-                .map(|c| match c {
-                    '\r' => '\u{0007}', // ASCII BEL
-                    '\n' => '\u{0001}', // ASCII SOH
-                    _ => c,
+                .iter()
+                .map(|byte| match byte {
+                    b'\r' => 0x07, // ASCII BEL
+                    b'\n' => 0x01, // ASCII SOH
+                    _ => *byte,
                 })
                 .collect(),
         }
     }
 }
+
+fn replace_bytes(input: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(index) = rest.windows(from.len()).position(|window| window == from) {
+        result.extend_from_slice(&rest[..index]);
+        result.extend_from_slice(to);
+        rest = &rest[index + from.len()..];
+    }
+    result.extend_from_slice(rest);
+
+    result
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+pub struct Timeouts {
+    pub connect: Duration,
+    pub write: Duration,
+    pub read: Duration,
+}
+
+impl Timeouts {
+    pub fn from_args(matches: &clap::ArgMatches) -> Self {
+        let millis = |id: &str| Duration::from_millis(*matches.get_one::<u64>(id).expect("Timeout has a default value"));
+
+        Self {
+            connect: millis("connect-timeout"),
+            write: millis("write-timeout"),
+            read: millis("read-timeout"),
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Duration::from_secs(5),
+            write: Duration::from_secs(5),
+            read: Duration::from_secs(10),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::http_client::error::{Phase, TransportError};
+use crate::http_client::timeouts::Timeouts;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme, StreamOwned};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct TlsOptions {
+    pub sni: Option<String>,
+    pub insecure: bool,
+    pub ca_file: Option<String>,
+}
+
+impl TlsOptions {
+    pub fn from_args(matches: &clap::ArgMatches) -> Self {
+        Self {
+            sni: matches.get_one::<String>("sni").cloned(),
+            insecure: matches.get_flag("insecure"),
+            ca_file: matches.get_one::<String>("ca-file").cloned(),
+        }
+    }
+}
+
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    sni: Option<String>,
+}
+
+impl TlsConnector {
+    pub fn new(options: &TlsOptions) -> Result<Self, String> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .expect("Unsupported TLS protocol versions");
+
+        let config = if options.insecure {
+            builder.dangerous().with_custom_certificate_verifier(Arc::new(NoVerification(provider))).with_no_client_auth()
+        } else {
+            builder.with_root_certificates(load_root_store(options.ca_file.as_deref())?).with_no_client_auth()
+        };
+
+        Ok(Self {
+            config: Arc::new(config),
+            sni: options.sni.clone(),
+        })
+    }
+
+    // Completes the handshake before returning, so its failures and duration belong to connecting rather than to the first write
+    pub fn connect(&self, host: &str, mut stream: TcpStream, timeouts: &Timeouts) -> Result<StreamOwned<ClientConnection, TcpStream>, TransportError> {
+        // The SNI override is also the name the certificate is verified against
+        let server_name = ServerName::try_from(self.sni.as_deref().unwrap_or(host).to_string()).map_err(|e| TransportError::Tls(e.to_string()))?;
+        let mut connection = ClientConnection::new(self.config.clone(), server_name).map_err(|e| TransportError::Tls(e.to_string()))?;
+
+        set_timeouts(&stream, timeouts.connect, timeouts.connect)?;
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream).map_err(|e| TransportError::from_io(e, Phase::Connect))?;
+        }
+        set_timeouts(&stream, timeouts.write, timeouts.read)?;
+
+        Ok(StreamOwned::new(connection, stream))
+    }
+}
+
+fn set_timeouts(stream: &TcpStream, write: Duration, read: Duration) -> Result<(), TransportError> {
+    stream.set_write_timeout(Some(write)).map_err(|e| TransportError::Io(e.to_string()))?;
+    stream.set_read_timeout(Some(read)).map_err(|e| TransportError::Io(e.to_string()))
+}
+
+fn load_root_store(ca_file: Option<&str>) -> Result<RootCertStore, String> {
+    let mut root_store = RootCertStore::empty();
+
+    match ca_file {
+        Some(path) => {
+            for cert in CertificateDer::pem_file_iter(path).map_err(|e| format!("Failed to open CA bundle {}: {}", path, e))? {
+                let cert = cert.map_err(|e| format!("Invalid certificate in CA bundle {}: {}", path, e))?;
+                root_store.add(cert).map_err(|e| format!("Failed to add CA certificate from {}: {}", path, e))?;
+            }
+        }
+        None => root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    Ok(root_store)
+}
+
+// Accepts any server certificate, for targets with self-signed or mismatched certificates
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(&self, _: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, _: &[u8], _: &CertificateDer<'_>, _: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(&self, _: &[u8], _: &CertificateDer<'_>, _: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_ca_file_is_an_error() {
+        let options = TlsOptions {
+            ca_file: Some(String::from("/nonexistent/wmap-ca.pem")),
+            ..TlsOptions::default()
+        };
+
+        let Err(e) = TlsConnector::new(&options) else {
+            panic!("A missing CA bundle was accepted");
+        };
+
+        assert!(e.starts_with("Failed to open CA bundle /nonexistent/wmap-ca.pem"));
+    }
+}
@@ -1,9 +1,14 @@
+use crate::baseline::BaselineDiff;
+use crate::features::{self, ResponseFeatures};
+use crate::http_client::error::Outcome;
+use crate::http_client::response::ParsedResponse;
+use crate::timing::{TimingStats, TimingSummary};
+use base64::prelude::{Engine, BASE64_STANDARD};
 use clap::ArgMatches;
 use lazy_static::lazy_static;
-use serde::Serialize;
-use serde_json;
+use serde::{Deserialize, Serialize};
 use std::fmt::Write as FmtWrite;
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
 use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
 
@@ -15,14 +20,20 @@ pub struct Logger {
 }
 
 impl Logger {
-    pub fn init(verbose: bool, output_file: Option<&str>, include_framework: bool, formatter: Arc<dyn OutputFormatter + Send + Sync>) {
+    pub fn init(verbose: bool, output_file: Option<&str>, append: bool, include_framework: bool, formatter: Arc<dyn OutputFormatter + Send + Sync>) {
         let mut logger = GLOBAL_LOGGER.lock().unwrap();
         logger.verbose = verbose;
         logger.include_framework = include_framework;
         logger.formatter = formatter;
 
         if let Some(file_path) = output_file {
-            let file = OpenOptions::new().write(true).truncate(true).create(true).open(file_path).expect("Failed to open log file");
+            let file = OpenOptions::new()
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .create(true)
+                .open(file_path)
+                .expect("Failed to open log file");
             logger.output_file = Some(Mutex::new(io::BufWriter::new(file)));
         }
     }
@@ -32,6 +43,8 @@ impl Logger {
         if let Some(ref output_file) = self.output_file {
             let mut writer = output_file.lock().unwrap();
             writeln!(writer, "{}", formatted).expect("Failed to write to log file");
+            // The logger is never dropped, so nothing would flush what is left in the buffer at exit
+            writer.flush().expect("Failed to flush log file");
         } else {
             println!("{}", formatted);
         }
@@ -48,11 +61,32 @@ impl Logger {
         if let Some(ref output_file) = self.output_file {
             let mut writer = output_file.lock().unwrap();
             writeln!(writer, "{}", formatted_results).expect("Failed to write formatted results to log file");
+            writer.flush().expect("Failed to flush log file");
         } else {
             println!("{}", formatted_results);
         }
     }
 
+    // Writes a single result right away, for formatters that can stream them
+    pub fn write_result(&self, result: &RequestResult) {
+        let Some(formatted_result) = self.formatter.format_one(result) else {
+            return;
+        };
+
+        if let Some(ref output_file) = self.output_file {
+            let mut writer = output_file.lock().unwrap();
+            writeln!(writer, "{}", formatted_result).expect("Failed to write result to log file");
+            // Flushed line by line so an interrupted run keeps every result written so far
+            writer.flush().expect("Failed to flush log file");
+        } else {
+            println!("{}", formatted_result);
+        }
+    }
+
+    pub fn streams_results(&self) -> bool {
+        self.formatter.streams()
+    }
+
     pub fn print_args(&self, matches: &ArgMatches) {
         if !self.verbose {
             return;
@@ -73,10 +107,16 @@ impl Logger {
                         writeln!(output, "  {}: {:?}", id_str, values).unwrap();
                     }
                 }
-            } else if matches.get_flag(id_str) {
-                writeln!(output, "  {}: true", id_str).unwrap();
-            } else {
-                writeln!(output, "  {}: false", id_str).unwrap();
+            } else if let Ok(Some(value)) = matches.try_get_one::<u64>(id_str) {
+                writeln!(output, "  {}: {}", id_str, value).unwrap();
+            } else if let Ok(Some(value)) = matches.try_get_one::<usize>(id_str) {
+                writeln!(output, "  {}: {}", id_str, value).unwrap();
+            } else if let Ok(Some(value)) = matches.try_get_one::<u32>(id_str) {
+                writeln!(output, "  {}: {}", id_str, value).unwrap();
+            } else if let Ok(Some(value)) = matches.try_get_one::<f64>(id_str) {
+                writeln!(output, "  {}: {}", id_str, value).unwrap();
+            } else if let Ok(Some(flag)) = matches.try_get_one::<bool>(id_str) {
+                writeln!(output, "  {}: {}", id_str, flag).unwrap();
             }
         }
 
@@ -93,9 +133,11 @@ pub fn initialize_logger(matches: &clap::ArgMatches) {
     let verbose = matches.get_flag("verbose");
     let output_file = matches.get_one::<String>("output").map(|s| s.as_str());
     let output_format = matches.get_one::<String>("output-format").unwrap();
-    let formatter = get_formatter(&output_format);
+    // A resumed run adds its results to those of the interrupted one
+    let append = matches.get_flag("resume");
+    let formatter = get_formatter(output_format);
 
-    Logger::init(verbose, output_file, false, formatter);
+    Logger::init(verbose, output_file, append, false, formatter);
 }
 
 impl Default for Logger {
@@ -134,6 +176,13 @@ macro_rules! log_args {
     };
 }
 
+#[macro_export]
+macro_rules! log_result {
+    ($result:expr) => {
+        $crate::logger::GLOBAL_LOGGER.lock().unwrap().write_result(&$result);
+    };
+}
+
 #[macro_export]
 macro_rules! log_formatted_results {
     ($results:expr) => {{
@@ -144,27 +193,116 @@ macro_rules! log_formatted_results {
 
 pub trait OutputFormatter: Send + Sync {
     fn format(&self, results: &[RequestResult]) -> String;
+
+    // Streaming formatters get each result as soon as its request completes instead of the whole run at the end
+    fn streams(&self) -> bool {
+        false
+    }
+
+    fn format_one(&self, _result: &RequestResult) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct RequestResult {
     pub request_index: u32,
+    pub url: String,
     pub mutation_description: String,
     pub request: String,
+    pub request_base64: String,
+    // None when the request was only crafted, as in a dry run
+    #[serde(flatten)]
+    pub exchange: Option<Exchange>,
+    // None for baselines, and when either side has no response
+    pub baseline_diff: Option<BaselineDiff>,
+    pub framework: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Exchange {
+    // Lossy display string, the base64 copy keeps binary and non-UTF-8 responses intact
     pub response: String,
+    pub response_base64: String,
+    // The response with dates, session IDs, tokens and the like masked by typed placeholders
+    pub normalized_response: Option<String>,
+    pub parsed_response: Option<ParsedResponse>,
     pub response_time: u128,
-    pub framework: Option<String>,
+    pub timing: TimingSummary,
+    pub outcome: Outcome,
+    pub throttled: bool,
 }
 
-#[derive(Serialize)]
-pub struct JSONEntry {
+// The fields of a result that can be read back from the json and jsonl output formats
+#[derive(Debug, Deserialize)]
+pub struct StoredResult {
     pub request_index: u32,
+    // Missing from results written before it was added
+    #[serde(default)]
+    pub url: String,
     pub mutation_description: String,
-    pub request: String,
-    pub response: String,
-    pub response_time: u128,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub framework: Option<String>,
+    pub response: Option<String>,
+    #[serde(default)]
+    pub response_base64: Option<String>,
+    #[serde(default)]
+    pub normalized_response: Option<String>,
+    pub response_time: Option<u128>,
+    pub parsed_response: Option<ParsedResponse>,
+    #[serde(default)]
+    pub baseline_diff: Option<BaselineDiff>,
+    #[serde(default)]
+    pub timing: Option<TimingSummary>,
+    #[serde(default)]
+    pub throttled: bool,
+}
+
+impl StoredResult {
+    // The exact response bytes, or the lossy display string for results written before the base64 copy was added
+    pub fn raw_response(&self) -> Option<Vec<u8>> {
+        match &self.response_base64 {
+            Some(encoded) => BASE64_STANDARD.decode(encoded).ok(),
+            None => self.response.as_ref().map(|response| response.as_bytes().to_vec()),
+        }
+    }
+
+    // Results without a response, as in a dry run, and throttled ones have no features
+    pub fn features(&self, excluded: &[String]) -> Option<ResponseFeatures> {
+        if self.throttled {
+            return None;
+        }
+
+        Some(ResponseFeatures {
+            baseline_diff: self.baseline_diff.clone(),
+            timing: self.timing.clone(),
+            ..ResponseFeatures::new(
+                self.request_index,
+                &self.url,
+                &self.mutation_description,
+                self.framework.as_deref(),
+                self.response_time?,
+                features::normalized_head(self.normalized_response.as_deref(), self.parsed_response.as_ref()).as_ref(),
+                excluded,
+            )
+        })
+    }
+}
+
+pub fn read_results(path: &str) -> Result<Vec<StoredResult>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read results file {}: {}", path, e))?;
+    let invalid = |e: serde_json::Error| format!("Invalid results file {}: {} (only json and jsonl output can be read)", path, e);
+
+    if content.trim_start().starts_with('[') {
+        serde_json::from_str(&content).map_err(invalid)
+    } else {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(invalid)
+    }
 }
 
 pub struct JsonFormatter;
@@ -175,6 +313,23 @@ impl OutputFormatter for JsonFormatter {
     }
 }
 
+// Lines come in the order requests are sent, which is not request_index order since baselines go first and framing mutations last
+pub struct JsonLinesFormatter;
+
+impl OutputFormatter for JsonLinesFormatter {
+    fn format(&self, results: &[RequestResult]) -> String {
+        results.iter().filter_map(|result| self.format_one(result)).collect::<Vec<String>>().join("\n")
+    }
+
+    fn streams(&self) -> bool {
+        true
+    }
+
+    fn format_one(&self, result: &RequestResult) -> Option<String> {
+        serde_json::to_string(result).ok()
+    }
+}
+
 pub struct CsvFormatter;
 
 impl OutputFormatter for CsvFormatter {
@@ -182,18 +337,49 @@ impl OutputFormatter for CsvFormatter {
         let mut csv_output = String::new();
 
         // Write header
-        writeln!(csv_output, "request_index,request,response,response_time,framework").unwrap();
+        writeln!(
+            csv_output,
+            "request_index,url,mutation_description,request,request_base64,response,response_base64,normalized_response,protocol_version,status_code,reason_phrase,body_length,body_sha256,response_time,samples,connect_us,tcp_connect_us,ttfb_us,total_us,ttfb_adjusted_us,total_adjusted_us,outcome,throttled,deviates,status_changed,headers_added,headers_removed,body_length_delta,body_similarity,framework"
+        )
+        .unwrap();
 
         // Write each result
         for result in results {
+            let exchange = result.exchange.as_ref();
+            let parsed = exchange.and_then(|e| e.parsed_response.as_ref());
+            let diff = result.baseline_diff.as_ref();
             writeln!(
                 csv_output,
-                "{},{},{},{},{},{}",
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
                 result.request_index,
-                result.mutation_description,
+                escape_csv_value(&result.url),
+                escape_csv_value(&result.mutation_description),
                 escape_csv_value(&result.request),
-                escape_csv_value(&result.response),
-                result.response_time,
+                result.request_base64,
+                exchange.map_or("".to_string(), |e| escape_csv_value(&e.response)),
+                exchange.map_or("", |e| e.response_base64.as_str()),
+                exchange.and_then(|e| e.normalized_response.as_deref()).map_or("".to_string(), escape_csv_value),
+                parsed.map_or("".to_string(), |p| escape_csv_value(&p.head.protocol_version)),
+                parsed.and_then(|p| p.head.status_code).map_or("".to_string(), |code| code.to_string()),
+                parsed.map_or("".to_string(), |p| escape_csv_value(&p.head.reason_phrase)),
+                parsed.map_or("".to_string(), |p| p.body_length.to_string()),
+                parsed.map_or("", |p| p.body_sha256.as_str()),
+                exchange.map_or("".to_string(), |e| e.response_time.to_string()),
+                exchange.map_or("".to_string(), |e| e.timing.samples.to_string()),
+                median(exchange.and_then(|e| e.timing.connect_us.as_ref())),
+                median(exchange.and_then(|e| e.timing.tcp_connect_us.as_ref())),
+                median(exchange.and_then(|e| e.timing.ttfb_us.as_ref())),
+                median(exchange.and_then(|e| e.timing.total_us.as_ref())),
+                exchange.and_then(|e| e.timing.ttfb_adjusted_us).map_or("".to_string(), |us| us.to_string()),
+                exchange.and_then(|e| e.timing.total_adjusted_us).map_or("".to_string(), |us| us.to_string()),
+                exchange.map_or("", |e| e.outcome.label()),
+                exchange.map_or("".to_string(), |e| e.throttled.to_string()),
+                diff.map_or("".to_string(), |d| d.deviates.to_string()),
+                diff.map_or("".to_string(), |d| d.status_changed.to_string()),
+                diff.map_or("".to_string(), |d| escape_csv_value(&d.headers_added.join(" "))),
+                diff.map_or("".to_string(), |d| escape_csv_value(&d.headers_removed.join(" "))),
+                diff.map_or("".to_string(), |d| d.body_length_delta.to_string()),
+                diff.map_or("".to_string(), |d| d.body_similarity.to_string()),
                 result.framework.as_deref().unwrap_or("")
             )
             .unwrap();
@@ -203,8 +389,13 @@ impl OutputFormatter for CsvFormatter {
     }
 }
 
+// The CSV output only carries the medians, the JSON outputs have every statistic
+fn median(stats: Option<&TimingStats>) -> String {
+    stats.map_or("".to_string(), |stats| stats.median.to_string())
+}
+
 // Helper function to escape CSV values
-fn escape_csv_value(value: &str) -> String {
+pub fn escape_csv_value(value: &str) -> String {
     let mut escaped = String::new();
     let mut in_quotes = false;
 
@@ -241,6 +432,7 @@ fn escape_csv_value(value: &str) -> String {
 fn get_formatter(output_format: &str) -> Arc<dyn OutputFormatter + Send + Sync> {
     match output_format {
         "json" => Arc::new(JsonFormatter),
+        "jsonl" => Arc::new(JsonLinesFormatter),
         "csv" => Arc::new(CsvFormatter),
         _ => Arc::new(JsonFormatter),
     }
@@ -1,21 +1,67 @@
 mod args;
+mod baseline;
+mod checkpoint;
+mod classifier;
+mod executor;
+mod features;
 mod fuzz;
 mod http_client;
 mod logger;
+mod mutator;
+mod normalizer;
+mod rate_limiter;
+mod signatures;
+mod timing;
 mod utils;
 
+use baseline::Baselines;
+use checkpoint::Checkpoint;
+use classifier::Detector;
+use executor::{PlannedRequest, RequestKey};
+use features::FeatureExport;
 use fuzz::Fuzzer;
-use http_client::spacing_type::SpacingType;
+use http_client::headers::Headers;
 use logger::RequestResult;
-use std::collections::BTreeMap;
-
-const DEFAULT_HTTP_VERSION: &str = "HTTP/1.1";
+use mutator::{CategoryFilter, Mutation, MutationContext, Mutator, MutatorRegistry};
+use normalizer::Normalizer;
+use rate_limiter::RateLimiter;
+use signatures::SignatureMatcher;
+use std::collections::HashSet;
+use std::process;
+use std::sync::{Arc, Mutex};
 
 fn main() {
     let matches = args::parse_args();
 
-    logger::initialize_logger(&matches);
-    log_args!(&matches);
+    let result = match matches.subcommand() {
+        Some(("train", matches)) => classifier::train(matches),
+        Some(("detect", matches)) => classifier::detect(matches),
+        _ => {
+            run_fuzzer(&matches);
+            Ok(())
+        }
+    };
+
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+}
+
+fn run_fuzzer(matches: &clap::ArgMatches) {
+    // A json array or a csv header appended after the interrupted run's output would leave a file nothing can read back
+    if matches.get_flag("resume") && matches.get_one::<String>("output-format").map(String::as_str) != Some("jsonl") {
+        eprintln!("--resume appends to the output file, which only works with --output-format jsonl");
+        process::exit(2);
+    }
+
+    logger::initialize_logger(matches);
+    http_client::initialize_client(matches).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    log_args!(matches);
+    executor::handle_interruptions();
 
     let urls_and_frameworks: Vec<String> = if let Some(input_file) = matches.get_one::<String>("input") {
         utils::read_urls_from_file(input_file)
@@ -25,143 +71,236 @@ fn main() {
 
     let methods: Vec<String> = matches.get_many::<String>("methods").expect("Methods are required").map(|s| s.to_string()).collect();
 
-    let mut results = Vec::new();
-    let mut request_index = 0;
+    let concurrency = *matches.get_one::<usize>("concurrency").expect("Concurrency has a default value");
+    let per_target_concurrency = matches.get_one::<usize>("per-target-concurrency").copied().unwrap_or(concurrency);
+
+    let registry = MutatorRegistry::with_builtins();
+    let mutators = mutator::from_args(matches, &registry).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    let definitions = Arc::new(mutator::definitions::from_args(matches).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    }));
+
+    let category_filter = CategoryFilter::from_args(matches);
 
-    for url_and_framework in &urls_and_frameworks {
+    // Crafts every request of one target. Payloads can be megabytes, so a target is crafted once to list its requests
+    // and again when its concurrent mutations are sent, and only the bytes of the targets being sent are held
+    let plan_target = |target_index: usize, url_and_framework: &String, request_index: u32| {
         let (url, framework) = utils::extract_url_and_framework(url_and_framework);
-        let (_, _, request_target) = http_client::parse_url(&url);
-        let headers: BTreeMap<String, String> = http_client::get_default_headers(&url);
 
-        let mut fuzzer = Fuzzer::new(methods.clone(), request_index);
-        process_requests_per_method(&mut fuzzer, &url, &request_target, &headers, framework.as_deref(), &mut results);
-        request_index = fuzzer.request_index;
-    }
+        let mut fuzzer = Fuzzer::new(methods.clone(), request_index, Arc::clone(&definitions));
+        let mut planned_requests = Vec::new();
+        process_requests_per_method(&mut fuzzer, &mutators, &category_filter, target_index, &url, framework.as_deref(), &mut planned_requests);
+        planned_requests
+    };
 
-    log_formatted_results!(results);
-}
+    let mut keys = Vec::new();
+    let mut baseline_requests = Vec::new();
+    let mut sequential_requests = Vec::new();
+    let mut first_request_indices = Vec::new();
+    let mut request_index = 0;
 
-fn process_requests_per_method(fuzzer: &mut Fuzzer, url: &str, request_target: &str, headers: &BTreeMap<String, String>, framework: Option<&str>, results: &mut Vec<RequestResult>) {
-    let methods = fuzzer.methods.clone();
+    for (target_index, url_and_framework) in urls_and_frameworks.iter().enumerate() {
+        first_request_indices.push(request_index);
+        for planned in plan_target(target_index, url_and_framework, request_index) {
+            keys.push(RequestKey::from(&planned));
+            request_index += 1;
+            // Baselines and the timing-sensitive framings are small and sent in phases of their own, so they are kept
+            if planned.baseline {
+                baseline_requests.push(planned);
+            } else if planned.sequential {
+                sequential_requests.push(planned);
+            }
+        }
+    }
 
-    for method in &methods {
-        process_mutated_methods(fuzzer, method, url, request_target, headers, framework, results);
-        process_mutated_request_targets(fuzzer, method, url, request_target, headers, framework, results);
-        process_mutated_http_versions(fuzzer, method, url, request_target, headers, framework, results);
-        process_mutated_headers(fuzzer, method, url, request_target, framework, results);
-        process_mutated_spacings(fuzzer, method, url, request_target, headers, framework, results);
+    for category in category_filter.unmatched(&keys) {
+        eprintln!("No planned mutation belongs to the category '{}'", category);
     }
-}
 
-fn process_mutated_methods(fuzzer: &mut Fuzzer, method: &str, url: &str, request_target: &str, headers: &BTreeMap<String, String>, framework: Option<&str>, results: &mut Vec<RequestResult>) {
-    let (descriptions, mutations) = fuzzer.fuzz_http_method(method);
-
-    for (description, mutation) in descriptions.iter().zip(mutations.iter()) {
-        let request = http_client::craft_request(&mutation, request_target, DEFAULT_HTTP_VERSION, headers, None);
-        let (response, response_time) = http_client::send_request(url, &request);
-        results.push(RequestResult {
-            request_index: fuzzer.request_index,
-            mutation_description: description.clone(),
-            request,
-            response,
-            response_time,
-            framework: framework.map(|f| f.to_string()),
-        });
-        fuzzer.request_index += 1;
+    // Show what would be sent and stop there
+    if matches.get_flag("list-mutations") {
+        for key in &keys {
+            log_print!("{}\t{}\t{}\t{}", key.request_index, key.url, key.method, key.mutation_description.escape_debug());
+        }
+        return;
     }
-}
 
-fn process_mutated_request_targets(fuzzer: &mut Fuzzer, method: &str, url: &str, request_target: &str, headers: &BTreeMap<String, String>, framework: Option<&str>, results: &mut Vec<RequestResult>) {
-    let (descriptions, mutations) = fuzzer.fuzz_request_target(request_target);
-
-    for (description, mutation) in descriptions.iter().zip(mutations.iter()) {
-        let request = http_client::craft_request(method, &mutation, DEFAULT_HTTP_VERSION, headers, None);
-        let (response, response_time) = http_client::send_request(url, &request);
-        results.push(RequestResult {
-            request_index: fuzzer.request_index,
-            mutation_description: description.clone(),
-            request,
-            response,
-            response_time,
-            framework: framework.map(|f| f.to_string()),
-        });
-        fuzzer.request_index += 1;
+    let checkpoint = Checkpoint::from_args(matches);
+    // Baselines finished in a previous run are only sent again to diff the remaining mutations, their results are not written twice
+    let mut replayed_baselines = HashSet::new();
+    if let Some(checkpoint) = &checkpoint {
+        if let Some(last_request_index) = checkpoint.last_request_index() {
+            log_print_verbose!("Resuming after request {}", last_request_index);
+        }
+        keys = checkpoint.skip_finished(keys);
+        replayed_baselines = keys.iter().filter(|key| key.baseline && checkpoint.is_finished(key)).map(|key| key.request_index).collect();
     }
-}
+    let pending: HashSet<u32> = keys.iter().map(|key| key.request_index).collect();
+
+    let rate_limiter = RateLimiter::from_args(matches);
+    let streaming = logger::GLOBAL_LOGGER.lock().unwrap().streams_results();
 
-fn process_mutated_http_versions(fuzzer: &mut Fuzzer, method: &str, url: &str, request_target: &str, headers: &BTreeMap<String, String>, framework: Option<&str>, results: &mut Vec<RequestResult>) {
-    let (descriptions, mutations) = fuzzer.fuzz_http_version(DEFAULT_HTTP_VERSION);
-
-    for (description, mutation) in descriptions.iter().zip(mutations.iter()) {
-        let request = http_client::craft_request(method, request_target, mutation, headers, None);
-        let (response, response_time) = http_client::send_request(url, &request);
-        results.push(RequestResult {
-            request_index: fuzzer.request_index,
-            mutation_description: description.clone(),
-            request,
-            response,
-            response_time,
-            framework: framework.map(|f| f.to_string()),
+    let feature_export = FeatureExport::from_args(matches);
+    let detector = Detector::from_args(matches).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let signature_matcher = SignatureMatcher::from_args(matches).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let normalizer = Normalizer::from_args(matches).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let dry_run = matches.get_flag("dry-run");
+    let samples = *matches.get_one::<usize>("samples").expect("Samples has a default value");
+    let only_deviations = matches.get_flag("only-deviations");
+
+    let baselines = Baselines::new(&keys);
+    drop(keys);
+    let baseline_requests = baseline_requests.into_iter().filter(|planned| pending.contains(&planned.request_index));
+    let sequential_requests = sequential_requests.into_iter().filter(|planned| pending.contains(&planned.request_index));
+    let concurrent_requests = urls_and_frameworks
+        .iter()
+        .zip(first_request_indices.iter().copied())
+        .enumerate()
+        .flat_map(|(target_index, (url_and_framework, request_index))| plan_target(target_index, url_and_framework, request_index))
+        .filter(|planned| !planned.baseline && !planned.sequential && pending.contains(&planned.request_index));
+
+    // Every baseline is sent before the mutations so each mutated response can be diffed as soon as it arrives, and
+    // timing-sensitive mutations are sent one at a time once the others have finished
+    let run = |on_result: &(dyn Fn(RequestResult) + Sync)| {
+        let phases: [(Box<dyn Iterator<Item = PlannedRequest> + Send + '_>, usize, usize); 3] = [
+            (Box::new(baseline_requests), concurrency, per_target_concurrency),
+            (Box::new(concurrent_requests), concurrency, per_target_concurrency),
+            (Box::new(sequential_requests), 1, 1),
+        ];
+        for (planned_requests, concurrency, per_target_concurrency) in phases {
+            if dry_run {
+                executor::dry_run(planned_requests, on_result);
+            } else {
+                executor::execute(planned_requests, concurrency, per_target_concurrency, &rate_limiter, samples, on_result);
+            }
+        }
+    };
+
+    // An interrupted run stops sending new requests, and what it gathered is still written out below
+    if streaming {
+        run(&|mut result| {
+            normalizer.apply(&mut result);
+            baselines.annotate(&mut result);
+            if replayed_baselines.contains(&result.request_index) {
+                return;
+            }
+            if let Some(feature_export) = &feature_export {
+                feature_export.record(&result);
+            }
+            if let Some(detector) = &detector {
+                detector.record(&result);
+            }
+            if let Some(signature_matcher) = &signature_matcher {
+                signature_matcher.record(&result);
+            }
+            if is_reported(&result, only_deviations) {
+                log_result!(result);
+            }
+            if let Some(checkpoint) = &checkpoint {
+                checkpoint.record(result.request_index);
+            }
+        });
+    } else {
+        let results = Mutex::new(Vec::new());
+        run(&|mut result| {
+            normalizer.apply(&mut result);
+            baselines.annotate(&mut result);
+            if !replayed_baselines.contains(&result.request_index) {
+                results.lock().unwrap().push(result);
+            }
         });
-        fuzzer.request_index += 1;
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|result| result.request_index);
+        if let Some(feature_export) = &feature_export {
+            results.iter().for_each(|result| feature_export.record(result));
+        }
+        if let Some(detector) = &detector {
+            results.iter().for_each(|result| detector.record(result));
+        }
+        if let Some(signature_matcher) = &signature_matcher {
+            results.iter().for_each(|result| signature_matcher.record(result));
+        }
+        let finished: Vec<u32> = results.iter().map(|result| result.request_index).collect();
+        results.retain(|result| is_reported(result, only_deviations));
+        log_formatted_results!(results);
+        if let Some(checkpoint) = &checkpoint {
+            finished.into_iter().for_each(|request_index| checkpoint.record(request_index));
+        }
     }
-}
 
-fn process_mutated_headers(fuzzer: &mut Fuzzer, method: &str, url: &str, request_target: &str, framework: Option<&str>, results: &mut Vec<RequestResult>) {
-    let (descriptions, mutations) = fuzzer.fuzz_headers(url);
-
-    for (description, mutation) in descriptions.iter().zip(mutations.iter()) {
-        let request = http_client::craft_request(method, request_target, DEFAULT_HTTP_VERSION, &mutation, None);
-        let (response, response_time) = http_client::send_request(url, &request);
-        results.push(RequestResult {
-            request_index: fuzzer.request_index,
-            mutation_description: description.clone(),
-            request,
-            response,
-            response_time,
-            framework: framework.map(|f| f.to_string()),
-        });
-        fuzzer.request_index += 1;
+    if let Some(feature_export) = feature_export {
+        feature_export.write();
+    }
+    if let Some(detector) = detector {
+        eprint!("{}", detector.report());
+    }
+    if let Some(signature_matcher) = signature_matcher {
+        eprint!("{}", signature_matcher.report());
     }
 }
 
-fn process_mutated_spacings(fuzzer: &mut Fuzzer, method: &str, url: &str, request_target: &str, headers: &BTreeMap<String, String>, framework: Option<&str>, results: &mut Vec<RequestResult>) {
-    let (descriptions, spacing_types) = (
-        vec![
-            String::from("[spacing] All spaces"),
-            String::from("[spacing] All tabs"),
-            String::from("[spacing] Double spaces"),
-            String::from("[spacing] Multiple spaces"),
-            String::from("[spacing] Null terminated"),
-            String::from("[spacing] Multiple line breaks"),
-            String::from("[spacing] Leading and trailing tabs"),
-            String::from("[spacing] Leading and trailing whitespaces"),
-            String::from("[spacing] BEL and SOH control chars instead of \r\n"),
-        ],
-        vec![
-            SpacingType::AllSpaces,
-            SpacingType::AllTabs,
-            SpacingType::DoubleSpaces,
-            SpacingType::MultipleSpaces,
-            SpacingType::NullTerminated,
-            SpacingType::MultipleLineBreaks,
-            SpacingType::LeadingTrailingTabs,
-            SpacingType::LeadingTrailingWhitespaces,
-            SpacingType::ControlChars,
-        ],
-    );
-
-    for (description, spacing_type) in descriptions.iter().zip(spacing_types.iter()) {
-        let request = http_client::craft_request(method, request_target, DEFAULT_HTTP_VERSION, headers, Some(&spacing_type));
-        let (response, response_time) = http_client::send_request(url, &request);
-        results.push(RequestResult {
-            request_index: fuzzer.request_index,
-            mutation_description: description.clone(),
-            request,
-            response,
-            response_time,
-            framework: framework.map(|f| f.to_string()),
-        });
-        fuzzer.request_index += 1;
+// Baselines, and results without a response to compare, are always reported
+fn is_reported(result: &RequestResult, only_deviations: bool) -> bool {
+    !only_deviations || result.baseline_diff.as_ref().is_none_or(|diff| diff.deviates)
+}
+
+fn process_requests_per_method(
+    fuzzer: &mut Fuzzer,
+    mutators: &[&dyn Mutator],
+    category_filter: &CategoryFilter,
+    target_index: usize,
+    url: &str,
+    framework: Option<&str>,
+    planned_requests: &mut Vec<PlannedRequest>,
+) {
+    let (_, _, request_target) = http_client::parse_url(url);
+    let headers: Headers = http_client::get_default_headers(url);
+    let methods = fuzzer.methods.clone();
+
+    for method in &methods {
+        let context = MutationContext {
+            fuzzer,
+            method,
+            url,
+            request_target: &request_target,
+            headers: &headers,
+        };
+        // The clean request every mutation of this method is compared with, whatever the filters
+        let baseline = mutator::builtin::baseline(&context);
+        let mutations: Vec<Mutation> = mutators
+            .iter()
+            .flat_map(|mutator| mutator.mutate(&context))
+            .filter(|mutation| category_filter.allows(&mutation.description))
+            .collect();
+
+        for (mutation, is_baseline) in std::iter::once((baseline, true)).chain(mutations.into_iter().map(|mutation| (mutation, false))) {
+            planned_requests.push(PlannedRequest {
+                request_index: fuzzer.request_index,
+                mutation_description: mutation.description,
+                url: url.to_string(),
+                target_index,
+                method: method.to_string(),
+                request: mutation.request,
+                framework: framework.map(|f| f.to_string()),
+                sequential: mutation.sequential,
+                baseline: is_baseline,
+            });
+            fuzzer.request_index += 1;
+        }
     }
 }
@@ -0,0 +1,244 @@
+use crate::http_client;
+use crate::http_client::spacing_type::SpacingType;
+use crate::mutator::{Mutation, MutationContext, Mutator};
+
+const DEFAULT_HTTP_VERSION: &str = "HTTP/1.1";
+
+pub fn mutators() -> Vec<Box<dyn Mutator>> {
+    vec![
+        Box::new(MethodMutator),
+        Box::new(RequestTargetMutator),
+        Box::new(HttpVersionMutator),
+        Box::new(HeaderMutator),
+        Box::new(HeaderNameMutator),
+        Box::new(BodyMutator),
+        Box::new(FramingMutator),
+        Box::new(SpacingMutator),
+        Box::new(RawBytesMutator),
+    ]
+}
+
+// Not a mutator: the unmodified request, sent before the mutations of its target and method
+pub fn baseline(context: &MutationContext) -> Mutation {
+    Mutation::new(
+        String::from("[baseline] Clean request"),
+        http_client::craft_request(context.method, context.request_target, DEFAULT_HTTP_VERSION, context.headers, None, None),
+    )
+}
+
+pub struct MethodMutator;
+
+impl Mutator for MethodMutator {
+    fn name(&self) -> &str {
+        "method"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_http_method(context.method);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, mutation)| {
+                Mutation::new(
+                    description,
+                    http_client::craft_request(mutation, context.request_target, DEFAULT_HTTP_VERSION, context.headers, None, None),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct RequestTargetMutator;
+
+impl Mutator for RequestTargetMutator {
+    fn name(&self) -> &str {
+        "request-target"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_request_target(context.request_target);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, mutation)| Mutation::new(description, http_client::craft_request(context.method, mutation, DEFAULT_HTTP_VERSION, context.headers, None, None)))
+            .collect()
+    }
+}
+
+pub struct HttpVersionMutator;
+
+impl Mutator for HttpVersionMutator {
+    fn name(&self) -> &str {
+        "http-version"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_http_version(DEFAULT_HTTP_VERSION);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, mutation)| Mutation::new(description, http_client::craft_request(context.method, context.request_target, mutation, context.headers, None, None)))
+            .collect()
+    }
+}
+
+pub struct HeaderMutator;
+
+impl Mutator for HeaderMutator {
+    fn name(&self) -> &str {
+        "header"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_headers(context.url);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, headers)| {
+                Mutation::new(
+                    description,
+                    http_client::craft_request(context.method, context.request_target, DEFAULT_HTTP_VERSION, headers, None, None),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct HeaderNameMutator;
+
+impl Mutator for HeaderNameMutator {
+    fn name(&self) -> &str {
+        "header-name"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_header_names(context.url);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, headers)| {
+                Mutation::new(
+                    description,
+                    http_client::craft_request(context.method, context.request_target, DEFAULT_HTTP_VERSION, headers, None, None),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct BodyMutator;
+
+impl Mutator for BodyMutator {
+    fn name(&self) -> &str {
+        "body"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_body(context.url);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, (headers, body))| {
+                Mutation::new(
+                    description,
+                    http_client::craft_request(context.method, context.request_target, DEFAULT_HTTP_VERSION, headers, None, Some(body.as_bytes())),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct FramingMutator;
+
+impl Mutator for FramingMutator {
+    fn name(&self) -> &str {
+        "framing"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_framing(context.url);
+
+        // Some framings make the server wait for bytes that never come, which concurrent load would blur
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, (headers, body))| {
+                Mutation::new(
+                    description,
+                    http_client::craft_request(context.method, context.request_target, DEFAULT_HTTP_VERSION, headers, None, Some(body.as_bytes())),
+                )
+                .sequential()
+            })
+            .collect()
+    }
+}
+
+pub struct SpacingMutator;
+
+impl Mutator for SpacingMutator {
+    fn name(&self) -> &str {
+        "spacing"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, spacing_types) = (
+            vec![
+                String::from("[spacing] All spaces"),
+                String::from("[spacing] All tabs"),
+                String::from("[spacing] Double spaces"),
+                String::from("[spacing] Multiple spaces"),
+                String::from("[spacing] Null terminated"),
+                String::from("[spacing] Multiple line breaks"),
+                String::from("[spacing] Leading and trailing tabs"),
+                String::from("[spacing] Leading and trailing whitespaces"),
+                String::from("[spacing] BEL and SOH control chars instead of \r\n"),
+            ],
+            vec![
+                SpacingType::AllSpaces,
+                SpacingType::AllTabs,
+                SpacingType::DoubleSpaces,
+                SpacingType::MultipleSpaces,
+                SpacingType::NullTerminated,
+                SpacingType::MultipleLineBreaks,
+                SpacingType::LeadingTrailingTabs,
+                SpacingType::LeadingTrailingWhitespaces,
+                SpacingType::ControlChars,
+            ],
+        );
+
+        descriptions
+            .into_iter()
+            .zip(spacing_types.iter())
+            .map(|(description, spacing_type)| {
+                Mutation::new(
+                    description,
+                    http_client::craft_request(context.method, context.request_target, DEFAULT_HTTP_VERSION, context.headers, Some(spacing_type), None),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct RawBytesMutator;
+
+impl Mutator for RawBytesMutator {
+    fn name(&self) -> &str {
+        "raw-bytes"
+    }
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation> {
+        let (descriptions, mutations) = context.fuzzer.fuzz_raw_bytes(context.method, context.request_target, context.url);
+
+        descriptions
+            .into_iter()
+            .zip(mutations.iter())
+            .map(|(description, (method, request_target, headers))| Mutation::new(description, http_client::craft_request(method, request_target, DEFAULT_HTTP_VERSION, headers, None, None)))
+            .collect()
+    }
+}
@@ -0,0 +1,172 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+const DEFAULT_DEFINITIONS: &str = include_str!("../../mutations/default.toml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Field {
+    Method,
+    RequestTarget,
+    // Parts of the request target, only mutated when the target has them
+    LastSegment,
+    Query,
+    Fragment,
+    HttpVersion,
+    Header,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    Replace,
+    Prefix,
+    Suffix,
+    SuffixAndPrefix,
+    InsertAt,
+    Substitute,
+    // Transformations of the original value, without payloads
+    Lowercase,
+    AlternateCase,
+    SpaceOut,
+}
+
+impl Operation {
+    fn takes_payloads(&self) -> bool {
+        !matches!(self, Operation::Lowercase | Operation::AlternateCase | Operation::SpaceOut)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Payload {
+    Text(String),
+    // Long payloads stay readable in the file
+    Repeated { value: String, repeat: usize },
+    // Only for the substitute operation
+    Substitution { from: String, to: String },
+}
+
+impl Payload {
+    fn render(&self) -> String {
+        match self {
+            Payload::Text(text) => text.clone(),
+            Payload::Repeated { value, repeat } => value.repeat(*repeat),
+            Payload::Substitution { to, .. } => to.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MutationDefinition {
+    pub field: Field,
+    pub header: Option<String>,
+    pub operation: Operation,
+    #[serde(default)]
+    pub position: usize,
+    #[serde(default)]
+    pub payloads: Vec<Payload>,
+}
+
+impl MutationDefinition {
+    // Applies every payload to `original`, expanding the given (placeholder, value) pairs first
+    pub fn apply(&self, original: &str, placeholders: &[(&str, &str)]) -> Vec<String> {
+        match self.operation {
+            Operation::Lowercase => return vec![original.to_lowercase()],
+            Operation::AlternateCase => return vec![alternate_case(original)],
+            Operation::SpaceOut => return vec![original.chars().map(String::from).collect::<Vec<String>>().join(" ")],
+            _ => {}
+        }
+
+        let mut mutated = Vec::new();
+        for payload in &self.payloads {
+            let rendered = placeholders.iter().fold(payload.render(), |rendered, (placeholder, value)| rendered.replace(placeholder, value));
+
+            match (self.operation, payload) {
+                (Operation::Replace, _) => mutated.push(rendered),
+                (Operation::Prefix, _) => mutated.push(format!("{}{}", rendered, original)),
+                (Operation::Suffix, _) => mutated.push(format!("{}{}", original, rendered)),
+                (Operation::SuffixAndPrefix, _) => {
+                    mutated.push(format!("{}{}", original, rendered));
+                    mutated.push(format!("{}{}", rendered, original));
+                }
+                (Operation::InsertAt, _) => {
+                    let split_at = original.char_indices().nth(self.position).map_or(original.len(), |(index, _)| index);
+                    mutated.push(format!("{}{}{}", &original[..split_at], rendered, &original[split_at..]));
+                }
+                // Substitutions that find nothing to replace would only repeat the original
+                (Operation::Substitute, Payload::Substitution { from, .. }) if original.contains(from.as_str()) => mutated.push(original.replace(from.as_str(), &rendered)),
+                _ => {}
+            }
+        }
+
+        mutated
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if (self.field == Field::Header) != self.header.is_some() {
+            return Err(String::from("must set `header` if and only if its field is \"header\""));
+        }
+        if self.operation.takes_payloads() == self.payloads.is_empty() {
+            return Err(String::from("must have payloads unless its operation is \"lowercase\", \"alternate-case\" or \"space-out\""));
+        }
+
+        let substitutions = self.payloads.iter().filter(|payload| matches!(payload, Payload::Substitution { .. })).count();
+        match self.operation {
+            Operation::Substitute if substitutions < self.payloads.len() => Err(String::from("must only have { from = \"...\", to = \"...\" } payloads for the substitute operation")),
+            Operation::Substitute => Ok(()),
+            _ if substitutions > 0 => Err(String::from("may only have { from = \"...\", to = \"...\" } payloads for the substitute operation")),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn alternate_case(input: &str) -> String {
+    input
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i % 2 == 0 { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct MutationFile {
+    #[serde(default, rename = "mutation")]
+    mutations: Vec<MutationDefinition>,
+}
+
+// Parses a definition file, as YAML if its extension says so and as TOML otherwise
+pub fn parse(content: &str, path: &Path) -> Result<Vec<MutationDefinition>, String> {
+    let file: MutationFile = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml") | Some("yml") => serde_norway::from_str(content).map_err(|e| format!("Invalid mutation file {}: {}", path.display(), e))?,
+        _ => toml::from_str(content).map_err(|e| format!("Invalid mutation file {}: {}", path.display(), e))?,
+    };
+
+    for (index, definition) in file.mutations.iter().enumerate() {
+        definition.validate().map_err(|e| format!("Invalid mutation file {}: mutation #{} {}", path.display(), index + 1, e))?;
+    }
+
+    Ok(file.mutations)
+}
+
+// Loads the given definition files in order, or the built-in definitions if there are none
+pub fn load(paths: &[String]) -> Result<Vec<MutationDefinition>, String> {
+    if paths.is_empty() {
+        return parse(DEFAULT_DEFINITIONS, Path::new("mutations/default.toml"));
+    }
+
+    let mut definitions = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read mutation file {}: {}", path, e))?;
+        definitions.extend(parse(&content, Path::new(path))?);
+    }
+
+    Ok(definitions)
+}
+
+pub fn from_args(matches: &clap::ArgMatches) -> Result<Vec<MutationDefinition>, String> {
+    let paths: Vec<String> = matches.get_many::<String>("mutations").map(|paths| paths.cloned().collect()).unwrap_or_default();
+    load(&paths)
+}
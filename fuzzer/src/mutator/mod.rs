@@ -0,0 +1,139 @@
+pub mod builtin;
+pub mod definitions;
+
+use crate::executor::RequestKey;
+use crate::fuzz::Fuzzer;
+use crate::http_client::headers::Headers;
+
+// What a mutator starts from: one target and one of the methods being fuzzed
+pub struct MutationContext<'a> {
+    pub fuzzer: &'a Fuzzer,
+    pub method: &'a str,
+    pub url: &'a str,
+    pub request_target: &'a str,
+    pub headers: &'a Headers,
+}
+
+pub struct Mutation {
+    pub description: String,
+    pub request: Vec<u8>,
+    // Timing-sensitive mutations are sent on their own, once the concurrent ones have finished
+    pub sequential: bool,
+}
+
+impl Mutation {
+    pub fn new(description: String, request: Vec<u8>) -> Self {
+        Self {
+            description,
+            request,
+            sequential: false,
+        }
+    }
+
+    pub fn sequential(mut self) -> Self {
+        self.sequential = true;
+        self
+    }
+}
+
+// A family of mutations, e.g. on the method or the headers. Each one crafts complete requests
+pub trait Mutator: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn mutate(&self, context: &MutationContext) -> Vec<Mutation>;
+}
+
+pub struct MutatorRegistry {
+    mutators: Vec<Box<dyn Mutator>>,
+}
+
+impl MutatorRegistry {
+    pub fn with_builtins() -> Self {
+        let mut registry = Self { mutators: Vec::new() };
+        for mutator in builtin::mutators() {
+            registry.register(mutator);
+        }
+        registry
+    }
+
+    // Mutators run in registration order. A mutator registered under an existing name replaces it
+    pub fn register(&mut self, mutator: Box<dyn Mutator>) {
+        match self.mutators.iter().position(|registered| registered.name() == mutator.name()) {
+            Some(position) => self.mutators[position] = mutator,
+            None => self.mutators.push(mutator),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.mutators.iter().map(|mutator| mutator.name()).collect()
+    }
+
+    // Keeps the enabled mutators (all of them if none is named) minus the disabled ones
+    pub fn select(&self, enabled: Option<&[String]>, disabled: &[String]) -> Result<Vec<&dyn Mutator>, String> {
+        let names = self.names();
+        if let Some(unknown) = enabled.unwrap_or_default().iter().chain(disabled).find(|name| !names.contains(&name.as_str())) {
+            return Err(format!("Unknown mutator '{}', available mutators are: {}", unknown, names.join(", ")));
+        }
+
+        Ok(self
+            .mutators
+            .iter()
+            .filter(|mutator| enabled.is_none_or(|enabled| enabled.iter().any(|name| name == mutator.name())))
+            .filter(|mutator| !disabled.iter().any(|name| name == mutator.name()))
+            .map(|mutator| mutator.as_ref())
+            .collect())
+    }
+}
+
+pub fn from_args<'a>(matches: &clap::ArgMatches, registry: &'a MutatorRegistry) -> Result<Vec<&'a dyn Mutator>, String> {
+    let enabled: Option<Vec<String>> = matches.get_many::<String>("mutators").map(|names| names.cloned().collect());
+    let disabled: Vec<String> = matches.get_many::<String>("disable-mutators").map(|names| names.cloned().collect()).unwrap_or_default();
+
+    registry.select(enabled.as_deref(), &disabled)
+}
+
+// Keeps mutations by the bracketed category their description starts with, e.g. `[http version]`
+pub struct CategoryFilter {
+    only: Option<Vec<String>>,
+    skip: Vec<String>,
+}
+
+impl CategoryFilter {
+    pub fn new(only: Option<Vec<String>>, skip: Vec<String>) -> Self {
+        Self {
+            only: only.map(|categories| categories.iter().map(|category| normalize_category(category)).collect()),
+            skip: skip.iter().map(|category| normalize_category(category)).collect(),
+        }
+    }
+
+    pub fn from_args(matches: &clap::ArgMatches) -> Self {
+        Self::new(
+            matches.get_many::<String>("only").map(|categories| categories.cloned().collect()),
+            matches.get_many::<String>("skip").map(|categories| categories.cloned().collect()).unwrap_or_default(),
+        )
+    }
+
+    pub fn allows(&self, description: &str) -> bool {
+        let category = category(description).map(normalize_category).unwrap_or_default();
+
+        self.only.as_ref().is_none_or(|only| only.contains(&category)) && !self.skip.contains(&category)
+    }
+
+    // Categories asked for with --only that no planned mutation belongs to, most likely typos
+    pub fn unmatched(&self, keys: &[RequestKey]) -> Vec<&str> {
+        let only = self.only.iter().flatten().filter(|requested| !self.skip.contains(requested));
+
+        only.filter(|requested| !keys.iter().any(|key| category(&key.mutation_description).map(normalize_category).as_ref() == Some(*requested)))
+            .map(|requested| requested.as_str())
+            .collect()
+    }
+}
+
+pub fn category(description: &str) -> Option<&str> {
+    description.strip_prefix('[')?.split_once(']').map(|(category, _)| category)
+}
+
+// `http version`, `http-version` and `HTTP_VERSION` all name the same category
+pub fn normalize_category(category: &str) -> String {
+    category.trim().to_lowercase().replace(['-', '_'], " ")
+}
@@ -0,0 +1,124 @@
+use crate::logger::RequestResult;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+const DEFAULT_RULES: &str = include_str!("../normalization/default.toml");
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleDefinition {
+    placeholder: String,
+    pattern: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleDefinition>,
+}
+
+// Masks what its pattern matches, or only the `value` group when the pattern has one
+pub struct NormalizationRule {
+    placeholder: String,
+    pattern: Regex,
+}
+
+impl NormalizationRule {
+    fn apply(&self, text: &str) -> String {
+        self.pattern
+            .replace_all(text, |captures: &Captures| {
+                let matched = captures.get(0).expect("Group 0 is the whole match");
+                match captures.name("value") {
+                    Some(value) => format!("{}{}{}", &text[matched.start()..value.start()], self.placeholder, &text[value.end()..matched.end()]),
+                    None => self.placeholder.clone(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+// Parses a rule file, as YAML if its extension says so and as TOML otherwise
+pub fn parse(content: &str, path: &Path) -> Result<Vec<NormalizationRule>, String> {
+    let file: RuleFile = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml") | Some("yml") => serde_norway::from_str(content).map_err(|e| format!("Invalid normalization file {}: {}", path.display(), e))?,
+        _ => toml::from_str(content).map_err(|e| format!("Invalid normalization file {}: {}", path.display(), e))?,
+    };
+
+    file.rules
+        .into_iter()
+        .enumerate()
+        .map(|(index, rule)| {
+            Ok(NormalizationRule {
+                pattern: Regex::new(&rule.pattern).map_err(|e| format!("Invalid normalization file {}: rule #{} ({}) {}", path.display(), index + 1, rule.placeholder, e))?,
+                placeholder: rule.placeholder,
+            })
+        })
+        .collect()
+}
+
+// Loads the given rule files in order, or the built-in rules if there are none
+pub fn load(paths: &[String]) -> Result<Vec<NormalizationRule>, String> {
+    if paths.is_empty() {
+        return parse(DEFAULT_RULES, Path::new("normalization/default.toml"));
+    }
+
+    let mut rules = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read normalization file {}: {}", path, e))?;
+        rules.extend(parse(&content, Path::new(path))?);
+    }
+
+    Ok(rules)
+}
+
+pub struct Normalizer {
+    rules: Vec<NormalizationRule>,
+}
+
+impl Normalizer {
+    pub fn new(rules: Vec<NormalizationRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn from_args(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let paths: Vec<String> = matches.get_many::<String>("normalization").map(|paths| paths.cloned().collect()).unwrap_or_default();
+        Ok(Self::new(load(&paths)?))
+    }
+
+    // Each rule runs on the output of the previous one
+    pub fn normalize(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_string(), |text, rule| rule.apply(&text))
+    }
+
+    // Fills in the normalized copy of the response next to the raw one
+    pub fn apply(&self, result: &mut RequestResult) {
+        if let Some(exchange) = &mut result.exchange {
+            exchange.normalized_response = Some(self.normalize(&exchange.response));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(text: &str) -> String {
+        Normalizer::new(load(&[]).unwrap()).normalize(text)
+    }
+
+    #[test]
+    fn timestamps_are_masked_after_a_time_key() {
+        assert_eq!(normalize(r#"{"iat": 1700000000, "exp":1700003600}"#), r#"{"iat": <TIMESTAMP>, "exp":<TIMESTAMP>}"#);
+        assert_eq!(normalize("/poll?ts=1700000000123&x=1"), "/poll?ts=<TIMESTAMP>&x=1");
+        assert_eq!(normalize("created_at='1700000000'"), "created_at='<TIMESTAMP>'");
+    }
+
+    #[test]
+    fn bare_ten_digit_numbers_are_kept() {
+        for text in ["Content-Length: 1073741824", r#"{"id": 1234567890}"#, "order 1999999999 shipped"] {
+            assert_eq!(normalize(text), text);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::http_client::response::ParsedResponse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+struct HostState {
+    next_allowed: Instant,
+    backoff: Duration,
+}
+
+// Paces requests per host and backs off when a host signals it is being overloaded
+pub struct RateLimiter {
+    min_interval: Duration,
+    pub max_retries: u32,
+    hosts: Mutex<HashMap<(String, u16), HostState>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: Option<f64>, delay: Duration, max_retries: u32) -> Self {
+        let rate_interval = requests_per_second.filter(|rate| *rate > 0.0).map_or(Duration::ZERO, |rate| Duration::from_secs_f64(1.0 / rate));
+
+        Self {
+            min_interval: rate_interval.max(delay),
+            max_retries,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_args(matches: &clap::ArgMatches) -> Self {
+        Self::new(
+            matches.get_one::<f64>("rate").copied(),
+            Duration::from_millis(*matches.get_one::<u64>("delay").expect("Delay has a default value")),
+            *matches.get_one::<u32>("max-retries").expect("Max retries has a default value"),
+        )
+    }
+
+    // Blocks until the host may receive another request. Slots are reserved up front so concurrent workers stay paced
+    pub fn acquire(&self, host: &(String, u16)) {
+        let slot = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let now = Instant::now();
+            let state = hosts.entry(host.clone()).or_insert(HostState {
+                next_allowed: now,
+                backoff: INITIAL_BACKOFF,
+            });
+
+            let slot = state.next_allowed.max(now);
+            state.next_allowed = slot + self.min_interval;
+            slot
+        };
+
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+
+    // Holds back every request to the host for the server-requested time, or an exponentially growing one
+    pub fn report_throttled(&self, host: &(String, u16), retry_after: Option<Duration>) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let now = Instant::now();
+        let state = hosts.entry(host.clone()).or_insert(HostState {
+            next_allowed: now,
+            backoff: INITIAL_BACKOFF,
+        });
+
+        let wait = retry_after.unwrap_or(state.backoff).min(MAX_BACKOFF);
+        state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+        state.next_allowed = state.next_allowed.max(now + wait);
+    }
+
+    pub fn report_success(&self, host: &(String, u16)) {
+        if let Some(state) = self.hosts.lock().unwrap().get_mut(host) {
+            state.backoff = INITIAL_BACKOFF;
+        }
+    }
+}
+
+pub fn is_throttled(response: &ParsedResponse) -> bool {
+    matches!(response.head.status_code, Some(429) | Some(503))
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+pub fn retry_after(response: &ParsedResponse) -> Option<Duration> {
+    let value = *response.head.header_values("Retry-After").first()?;
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value).ok().map(|date| date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO)),
+    }
+}
@@ -0,0 +1,246 @@
+use crate::http_client::response::ParsedResponse;
+use crate::logger::{RequestResult, StoredResult};
+use crate::mutator;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use regex::bytes::Regex as BytesRegex;
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+const DEFAULT_SIGNATURES: &str = include_str!("../signatures/default.toml");
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignatureDefinition {
+    name: String,
+    framework: String,
+    #[serde(default = "default_weight")]
+    weight: f64,
+    mutation: Option<String>,
+    category: Option<String>,
+    status: Option<u16>,
+    header: Option<String>,
+    value: Option<String>,
+    body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignatureFile {
+    #[serde(default, rename = "signature")]
+    signatures: Vec<SignatureDefinition>,
+}
+
+// A rule naming a framework when one response matches all of its conditions
+pub struct Signature {
+    pub name: String,
+    pub framework: String,
+    pub weight: f64,
+    mutation: Option<String>,
+    category: Option<String>,
+    status: Option<u16>,
+    header: Option<String>,
+    value: Option<Regex>,
+    body: Option<BytesRegex>,
+}
+
+impl Signature {
+    fn compile(definition: SignatureDefinition, path: &Path, index: usize) -> Result<Self, String> {
+        let invalid = |reason: String| format!("Invalid signature file {}: signature #{} ({}) {}", path.display(), index + 1, definition.name, reason);
+
+        if definition.value.is_some() && definition.header.is_none() {
+            return Err(invalid(String::from("sets `value` without `header`")));
+        }
+        if definition.status.is_none() && definition.header.is_none() && definition.body.is_none() {
+            return Err(invalid(String::from("must check the status, a header or the body")));
+        }
+
+        let value = definition.value.as_deref().map(Regex::new).transpose().map_err(|e| invalid(e.to_string()))?;
+        let body = definition.body.as_deref().map(BytesRegex::new).transpose().map_err(|e| invalid(e.to_string()))?;
+
+        Ok(Self {
+            name: definition.name,
+            framework: definition.framework,
+            weight: definition.weight,
+            mutation: definition.mutation,
+            category: definition.category.as_deref().map(mutator::normalize_category),
+            status: definition.status,
+            header: definition.header,
+            value,
+            body,
+        })
+    }
+
+    pub fn matches(&self, mutation_description: &str, parsed: Option<&ParsedResponse>, body: &[u8]) -> bool {
+        if self.mutation.as_ref().is_some_and(|mutation| mutation != mutation_description) {
+            return false;
+        }
+        if self
+            .category
+            .as_ref()
+            .is_some_and(|category| mutator::category(mutation_description).map(mutator::normalize_category).as_ref() != Some(category))
+        {
+            return false;
+        }
+
+        // Nothing can match a response that never came
+        let Some(parsed) = parsed else {
+            return false;
+        };
+        if self.status.is_some_and(|status| parsed.head.status_code != Some(status)) {
+            return false;
+        }
+        if let Some(header) = &self.header {
+            let values = parsed.head.header_values(header);
+            if values.is_empty() || self.value.as_ref().is_some_and(|value| !values.iter().any(|candidate| value.is_match(candidate))) {
+                return false;
+            }
+        }
+
+        self.body.as_ref().is_none_or(|pattern| pattern.is_match(body))
+    }
+}
+
+// Parses a signature file, as YAML if its extension says so and as TOML otherwise
+pub fn parse(content: &str, path: &Path) -> Result<Vec<Signature>, String> {
+    let file: SignatureFile = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml") | Some("yml") => serde_norway::from_str(content).map_err(|e| format!("Invalid signature file {}: {}", path.display(), e))?,
+        _ => toml::from_str(content).map_err(|e| format!("Invalid signature file {}: {}", path.display(), e))?,
+    };
+
+    file.signatures.into_iter().enumerate().map(|(index, definition)| Signature::compile(definition, path, index)).collect()
+}
+
+// Loads the given signature files in order, or the built-in signatures if there are none
+pub fn load(paths: &[String]) -> Result<Vec<Signature>, String> {
+    if paths.is_empty() {
+        return parse(DEFAULT_SIGNATURES, Path::new("signatures/default.toml"));
+    }
+
+    let mut signatures = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read signature file {}: {}", path, e))?;
+        signatures.extend(parse(&content, Path::new(path))?);
+    }
+
+    Ok(signatures)
+}
+
+struct TargetMatches {
+    url: String,
+    responses: usize,
+    // Per signature, the first response that matched it as (request index, mutation description)
+    matched: Vec<Option<(u32, String)>>,
+}
+
+// Scores every target against the signatures, response by response
+pub struct SignatureMatcher {
+    signatures: Vec<Signature>,
+    targets: Mutex<Vec<TargetMatches>>,
+}
+
+impl SignatureMatcher {
+    pub fn new(signatures: Vec<Signature>) -> Self {
+        Self {
+            signatures,
+            targets: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn from_args(matches: &clap::ArgMatches) -> Result<Option<Self>, String> {
+        let Some(paths) = matches.get_many::<String>("signatures") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self::new(load(&paths.cloned().collect::<Vec<String>>())?)))
+    }
+
+    pub fn observe(&self, request_index: u32, url: &str, mutation_description: &str, parsed: Option<&ParsedResponse>, body: &[u8]) {
+        let mut targets = self.targets.lock().unwrap();
+        let position = match targets.iter().position(|target| target.url == url) {
+            Some(position) => position,
+            None => {
+                targets.push(TargetMatches {
+                    url: url.to_string(),
+                    responses: 0,
+                    matched: vec![None; self.signatures.len()],
+                });
+                targets.len() - 1
+            }
+        };
+
+        let target = &mut targets[position];
+        target.responses += 1;
+        for (signature, matched) in self.signatures.iter().zip(target.matched.iter_mut()) {
+            // Results can arrive out of order, the report names the earliest request
+            if matched.as_ref().is_some_and(|(first, _)| *first < request_index) || !signature.matches(mutation_description, parsed, body) {
+                continue;
+            }
+            *matched = Some((request_index, mutation_description.to_string()));
+        }
+    }
+
+    // Results without a response, as in a dry run, match nothing. Body patterns run on the exact bytes rather than
+    // the lossy display string, which would turn any non-UTF-8 byte into a replacement character
+    pub fn record(&self, result: &RequestResult) {
+        if let Some(exchange) = &result.exchange {
+            let raw = BASE64_STANDARD.decode(&exchange.response_base64).unwrap_or_default();
+            let body = ParsedResponse::body(&raw).unwrap_or_default();
+            self.observe(result.request_index, &result.url, &result.mutation_description, exchange.parsed_response.as_ref(), &body);
+        }
+    }
+
+    pub fn record_stored(&self, result: &StoredResult) {
+        if let Some(raw) = result.raw_response() {
+            let body = ParsedResponse::body(&raw).unwrap_or_default();
+            self.observe(result.request_index, &result.url, &result.mutation_description, result.parsed_response.as_ref(), &body);
+        }
+    }
+
+    // Score of every framework with at least one matching signature, and which signatures matched on which mutation
+    pub fn report(self) -> String {
+        let mut output = String::new();
+        for target in self.targets.into_inner().unwrap() {
+            let url = if target.url.is_empty() { "(unknown target)" } else { &target.url };
+            let matched: Vec<(&Signature, &(u32, String))> = self
+                .signatures
+                .iter()
+                .zip(&target.matched)
+                .filter_map(|(signature, matched)| Some((signature, matched.as_ref()?)))
+                .collect();
+            writeln!(output, "{}: {} of {} signatures matched in {} responses", url, matched.len(), self.signatures.len(), target.responses).unwrap();
+
+            let mut frameworks: Vec<(&str, f64)> = Vec::new();
+            for (signature, _) in &matched {
+                match frameworks.iter_mut().find(|(framework, _)| *framework == signature.framework) {
+                    Some((_, score)) => *score += signature.weight,
+                    None => frameworks.push((&signature.framework, signature.weight)),
+                }
+            }
+            frameworks.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+
+            for (framework, score) in frameworks {
+                writeln!(output, "  {:<20} {:.1}", framework, score).unwrap();
+                for (signature, (request_index, mutation_description)) in matched.iter().filter(|(signature, _)| signature.framework == framework) {
+                    writeln!(
+                        output,
+                        "    {} (+{:.1}) on request {} {}",
+                        signature.name,
+                        signature.weight,
+                        request_index,
+                        mutation_description.escape_debug()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        output
+    }
+}
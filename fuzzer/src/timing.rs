@@ -0,0 +1,78 @@
+use crate::http_client::response::Timing;
+use serde::{Deserialize, Serialize};
+
+// Microseconds over every sample of one request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingStats {
+    pub min: u64,
+    pub median: u64,
+    pub p90: u64,
+    pub stddev: u64,
+}
+
+impl TimingStats {
+    pub fn from_values(mut values: Vec<u64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+
+        let count = values.len();
+        let median = if count.is_multiple_of(2) {
+            (values[count / 2 - 1] + values[count / 2]) / 2
+        } else {
+            values[count / 2]
+        };
+        // Nearest rank
+        let p90 = values[(count * 9).div_ceil(10) - 1];
+        let mean = values.iter().sum::<u64>() as f64 / count as f64;
+        let variance = values.iter().map(|value| (*value as f64 - mean).powi(2)).sum::<f64>() / count as f64;
+
+        Some(Self {
+            min: values[0],
+            median,
+            p90,
+            stddev: variance.sqrt().round() as u64,
+        })
+    }
+}
+
+// Connecting covers the TCP connection and, for https targets, the whole TLS handshake. Time to first byte runs from the start of
+// the request write and the total covers both
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingSummary {
+    pub samples: usize,
+    pub connect_us: Option<TimingStats>,
+    // The TCP connection alone, without the TLS handshake
+    pub tcp_connect_us: Option<TimingStats>,
+    // None when no sample got a byte back
+    pub ttfb_us: Option<TimingStats>,
+    pub total_us: Option<TimingStats>,
+    // Round trip estimate of the target: the fastest TCP connect of its baseline request. A TLS handshake takes
+    // more than one round trip plus the server's own work, so it is left out
+    pub network_baseline_us: Option<u64>,
+    // Medians minus the network baseline, comparable across networks
+    pub ttfb_adjusted_us: Option<u64>,
+    pub total_adjusted_us: Option<u64>,
+}
+
+impl TimingSummary {
+    pub fn from_samples(samples: &[Timing]) -> Self {
+        Self {
+            samples: samples.len(),
+            connect_us: TimingStats::from_values(samples.iter().map(|sample| sample.connect).collect()),
+            tcp_connect_us: TimingStats::from_values(samples.iter().filter_map(|sample| sample.tcp_connect).collect()),
+            ttfb_us: TimingStats::from_values(samples.iter().filter_map(|sample| sample.first_byte).collect()),
+            total_us: TimingStats::from_values(samples.iter().map(|sample| sample.total).collect()),
+            network_baseline_us: None,
+            ttfb_adjusted_us: None,
+            total_adjusted_us: None,
+        }
+    }
+
+    pub fn subtract_network_baseline(&mut self, network_baseline: u64) {
+        self.network_baseline_us = Some(network_baseline);
+        self.ttfb_adjusted_us = self.ttfb_us.as_ref().map(|ttfb| ttfb.median.saturating_sub(network_baseline));
+        self.total_adjusted_us = self.total_us.as_ref().map(|total| total.median.saturating_sub(network_baseline));
+    }
+}
@@ -1,4 +1,3 @@
-use crate::logger;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 